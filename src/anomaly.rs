@@ -0,0 +1,120 @@
+/*!
+flags samples the learned factors can't explain.
+
+a sample is anomalous if its reconstruction error
+(see `NMFBlas::sample_errors` and `OnlineNMF::column_errors`)
+is far above the errors of a reference window of normal samples.
+"far above" is measured robustly with the median
+and the median absolute deviation (MAD) such that
+a few anomalies in the reference window don't shift the threshold.
+*/
+
+use std::collections::VecDeque;
+
+use nmf_blas::FloatT;
+
+/// scales the MAD such that it estimates the standard deviation
+/// of normally distributed values
+pub const MAD_TO_STANDARD_DEVIATION: FloatT = 1.4826;
+
+/// returns the median of `values`
+pub fn median(values: &[FloatT]) -> FloatT {
+    assert!(!values.is_empty());
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let middle = sorted.len() / 2;
+    if 0 == sorted.len() % 2 {
+        (sorted[middle - 1] + sorted[middle]) / 2.
+    } else {
+        sorted[middle]
+    }
+}
+
+/// returns the median absolute deviation from the median of `values`
+pub fn median_absolute_deviation(values: &[FloatT]) -> FloatT {
+    let center = median(values);
+    let deviations: Vec<FloatT> = values.iter().map(|x| (x - center).abs()).collect();
+    median(&deviations[..])
+}
+
+/// returns `median + nmads * 1.4826 * MAD` of `reference`
+pub fn robust_threshold(reference: &[FloatT], nmads: FloatT) -> FloatT {
+    median(reference) + nmads * MAD_TO_STANDARD_DEVIATION * median_absolute_deviation(reference)
+}
+
+/// flags anomalous errors in a stream of batches.
+/// keeps a window of the most recent normal errors as reference.
+pub struct AnomalyDetector {
+    /// maximum number of errors in the reference window
+    pub window_size: usize,
+    /// how many (scaled) MADs above the median an error is anomalous.
+    /// `3` is a common choice.
+    pub nmads: FloatT,
+    /// the most recent errors that were not flagged
+    pub reference: VecDeque<FloatT>,
+}
+
+impl AnomalyDetector {
+    pub fn new(window_size: usize, nmads: FloatT) -> AnomalyDetector {
+        assert!(0 < window_size);
+        AnomalyDetector {
+            window_size: window_size,
+            nmads: nmads,
+            reference: VecDeque::with_capacity(window_size),
+        }
+    }
+
+    /// returns the current threshold.
+    /// `None` while the reference window is empty.
+    pub fn threshold(&self) -> Option<FloatT> {
+        if self.reference.is_empty() {
+            return None;
+        }
+        let reference: Vec<FloatT> = self.reference.iter().cloned().collect();
+        Some(robust_threshold(&reference[..], self.nmads))
+    }
+
+    /// returns for each of `errors` whether it is anomalous.
+    /// the first batch is judged against itself.
+    /// afterwards the errors that were not flagged
+    /// are added to the reference window.
+    pub fn detect(&mut self, errors: &[FloatT]) -> Vec<bool> {
+        if errors.is_empty() {
+            return Vec::new();
+        }
+        let threshold = self.threshold()
+            .unwrap_or_else(|| robust_threshold(errors, self.nmads));
+
+        let flags: Vec<bool> = errors.iter().map(|&error| threshold < error).collect();
+
+        for (&error, &flag) in errors.iter().zip(flags.iter()) {
+            if !flag {
+                if self.reference.len() == self.window_size {
+                    self.reference.pop_front();
+                }
+                self.reference.push_back(error);
+            }
+        }
+        flags
+    }
+}
+
+#[test]
+fn test_median_and_mad() {
+    assert_eq!(median(&[3., 1., 2.]), 2.);
+    assert_eq!(median(&[4., 1., 2., 3.]), 2.5);
+    // deviations from 2: 1, 1, 0, 2, 98 -> median 1
+    assert_eq!(median_absolute_deviation(&[1., 3., 2., 4., 100.]), 1.);
+}
+
+#[test]
+fn test_anomaly_detector() {
+    let mut detector = AnomalyDetector::new(100, 3.);
+    let flags = detector.detect(&[1., 1.1, 0.9, 1., 50.]);
+    assert_eq!(flags, vec![false, false, false, false, true]);
+    assert_eq!(detector.reference.len(), 4);
+
+    let flags = detector.detect(&[1.05, 20.]);
+    assert_eq!(flags, vec![false, true]);
+    assert_eq!(detector.reference.len(), 5);
+}
@@ -0,0 +1,528 @@
+/*!
+separates sound sources with NMF.
+
+1. `read_wav` reads a signal
+2. `stft` computes its short-time Fourier transform.
+   the magnitudes are the `samples`:
+   one frame per sample, one frequency bin per observed variable.
+3. `NMFBlas::iterate_divergence` factorizes the magnitudes.
+   each row of `hidden` is the spectrum of one source.
+   `Divergence::KullbackLeibler` usually works best for magnitudes.
+4. `wiener_masks` gives each source the fraction of each
+   time-frequency bin its factor explains
+5. `Stft::masked` and `Stft::inverse` (overlap-add) resynthesize each source
+6. `write_wav` writes each source
+
+`separate` does steps 2 to 5.
+*/
+
+use std;
+use std::io::{Read, Write};
+use std::fs::File;
+use std::path::Path;
+use std::f64::consts::PI;
+
+use num::{Complex, Zero};
+use rand::Rng;
+
+use helpers::Array2D;
+use nmf_blas::{NMFBlas, Divergence, FloatT, ShapeAsTuple, DIVERGENCE_EPSILON};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum WavError {
+        /// an error has occured when reading the file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// the file is not a RIFF WAVE file
+        NotWav {}
+        /// the file has no `fmt ` or no `data` chunk or they are cut off
+        Truncated {}
+        /// the sample format is not PCM with 8, 16, 24 or 32 bits or 32 bit float
+        Unsupported(format: u16, bits_per_sample: u16) {
+            display("unsupported wav format {} with {} bits per sample", format, bits_per_sample)
+        }
+    }
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// a signal with one or more channels.
+/// values are in `-1..1`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub sample_rate: u32,
+    /// one `Vec` of values per channel. all have the same length.
+    pub channels: Vec<Vec<FloatT>>,
+}
+
+impl Audio {
+    pub fn new_mono(sample_rate: u32, signal: Vec<FloatT>) -> Audio {
+        Audio {
+            sample_rate: sample_rate,
+            channels: vec![signal],
+        }
+    }
+
+    /// returns the average of all channels
+    pub fn mono(&self) -> Vec<FloatT> {
+        assert!(!self.channels.is_empty());
+        let nchannels = self.channels.len() as FloatT;
+        (0..self.channels[0].len())
+            .map(|i| self.channels.iter().fold(0., |sum, channel| sum + channel[i]) / nchannels)
+            .collect()
+    }
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> u16 {
+    (bytes[offset] as u16) | ((bytes[offset + 1] as u16) << 8)
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    (le_u16(bytes, offset) as u32) | ((le_u16(bytes, offset + 2) as u32) << 16)
+}
+
+/// reads a PCM or float WAV file
+pub fn read_wav<R: Read>(reader: &mut R) -> Result<Audio, WavError> {
+    let mut bytes = Vec::new();
+    try!(reader.read_to_end(&mut bytes));
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::NotWav);
+    }
+
+    // (format, nchannels, sample_rate, bits_per_sample)
+    let mut format: Option<(u16, usize, u32, u16)> = None;
+    let mut data: Option<&[u8]> = None;
+
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = le_u32(&bytes, offset + 4) as usize;
+        let start = offset + 8;
+        if bytes.len() < start + size {
+            return Err(WavError::Truncated);
+        }
+        let chunk = &bytes[start..start + size];
+        if id == b"fmt " {
+            if size < 16 {
+                return Err(WavError::Truncated);
+            }
+            let mut tag = le_u16(chunk, 0);
+            if WAVE_FORMAT_EXTENSIBLE == tag && 26 <= size {
+                // the actual format is the start of the subformat GUID
+                tag = le_u16(chunk, 24);
+            }
+            format = Some((tag, le_u16(chunk, 2) as usize, le_u32(chunk, 4), le_u16(chunk, 14)));
+        } else if id == b"data" {
+            data = Some(chunk);
+        }
+        // chunks are padded to an even size
+        offset = start + size + size % 2;
+    }
+
+    let (tag, nchannels, sample_rate, bits_per_sample) = try!(format.ok_or(WavError::Truncated));
+    let data = try!(data.ok_or(WavError::Truncated));
+    if 0 == nchannels {
+        return Err(WavError::Truncated);
+    }
+
+    let nbytes = (bits_per_sample / 8) as usize;
+    let decode: fn(&[u8]) -> FloatT = match (tag, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => decode_u8,
+        (WAVE_FORMAT_PCM, 16) => decode_i16,
+        (WAVE_FORMAT_PCM, 24) => decode_i24,
+        (WAVE_FORMAT_PCM, 32) => decode_i32,
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => decode_f32,
+        _ => return Err(WavError::Unsupported(tag, bits_per_sample)),
+    };
+
+    let nframes = data.len() / (nbytes * nchannels);
+    let mut channels = vec![Vec::with_capacity(nframes); nchannels];
+    for iframe in 0..nframes {
+        for (ichannel, channel) in channels.iter_mut().enumerate() {
+            let start = (iframe * nchannels + ichannel) * nbytes;
+            channel.push(decode(&data[start..start + nbytes]));
+        }
+    }
+
+    Ok(Audio {
+        sample_rate: sample_rate,
+        channels: channels,
+    })
+}
+
+fn decode_u8(bytes: &[u8]) -> FloatT {
+    (bytes[0] as FloatT - 128.) / 128.
+}
+
+fn decode_i16(bytes: &[u8]) -> FloatT {
+    le_u16(bytes, 0) as i16 as FloatT / 32768.
+}
+
+fn decode_i24(bytes: &[u8]) -> FloatT {
+    // shift into the upper bytes of an i32 to get the sign right
+    let value = ((bytes[0] as u32) << 8) | ((bytes[1] as u32) << 16) | ((bytes[2] as u32) << 24);
+    (value as i32 >> 8) as FloatT / 8388608.
+}
+
+fn decode_i32(bytes: &[u8]) -> FloatT {
+    (le_u32(bytes, 0) as i32 as f64 / 2147483648.) as FloatT
+}
+
+fn decode_f32(bytes: &[u8]) -> FloatT {
+    let bits = le_u32(bytes, 0);
+    unsafe { std::mem::transmute::<u32, f32>(bits) as FloatT }
+}
+
+pub fn load_wav<P: AsRef<Path>>(path: P) -> Result<Audio, WavError> {
+    let mut file = try!(File::open(path));
+    read_wav(&mut file)
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> std::io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> std::io::Result<()> {
+    try!(write_u16(writer, value as u16));
+    write_u16(writer, (value >> 16) as u16)
+}
+
+/// writes `audio` as a 16 bit PCM WAV file.
+/// values outside of `-1..1` are clipped.
+pub fn write_wav<W: Write>(writer: &mut W, audio: &Audio) -> std::io::Result<()> {
+    let nchannels = audio.channels.len();
+    assert!(0 < nchannels);
+    let nframes = audio.channels[0].len();
+    assert!(audio.channels.iter().all(|channel| channel.len() == nframes));
+
+    let block_align = 2 * nchannels as u32;
+    let data_size = block_align * nframes as u32;
+
+    try!(writer.write_all(b"RIFF"));
+    try!(write_u32(writer, 36 + data_size));
+    try!(writer.write_all(b"WAVE"));
+
+    try!(writer.write_all(b"fmt "));
+    try!(write_u32(writer, 16));
+    try!(write_u16(writer, WAVE_FORMAT_PCM));
+    try!(write_u16(writer, nchannels as u16));
+    try!(write_u32(writer, audio.sample_rate));
+    try!(write_u32(writer, audio.sample_rate * block_align));
+    try!(write_u16(writer, block_align as u16));
+    try!(write_u16(writer, 16));
+
+    try!(writer.write_all(b"data"));
+    try!(write_u32(writer, data_size));
+    for iframe in 0..nframes {
+        for channel in audio.channels.iter() {
+            let value = channel[iframe].max(-1.).min(1.);
+            try!(write_u16(writer, (value * 32767.).round() as i16 as u16));
+        }
+    }
+    Ok(())
+}
+
+pub fn save_wav<P: AsRef<Path>>(path: P, audio: &Audio) -> std::io::Result<()> {
+    let mut file = try!(File::create(path));
+    write_wav(&mut file, audio)
+}
+
+/// in place radix 2 fast fourier transform.
+/// the length of `values` must be a power of 2.
+/// the inverse transform is scaled by `1 / n`.
+pub fn fft(values: &mut [Complex<FloatT>], inverse: bool) {
+    let n = values.len();
+    assert!(n.is_power_of_two());
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while 0 != j & bit {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1. } else { -1. };
+    let mut length = 2;
+    while length <= n {
+        let angle = sign * 2. * PI / length as f64;
+        let root = Complex::new(angle.cos() as FloatT, angle.sin() as FloatT);
+        for start in (0..n).filter(|start| 0 == start % length) {
+            let mut twiddle = Complex::new(1., 0.);
+            for k in 0..length / 2 {
+                let even = values[start + k];
+                let odd = values[start + k + length / 2] * twiddle;
+                values[start + k] = even + odd;
+                values[start + k + length / 2] = even - odd;
+                twiddle = twiddle * root;
+            }
+        }
+        length <<= 1;
+    }
+
+    if inverse {
+        let scale = 1. / n as FloatT;
+        for value in values.iter_mut() {
+            *value = Complex::new(value.re * scale, value.im * scale);
+        }
+    }
+}
+
+/// returns the periodic hann window of `size`
+pub fn hann(size: usize) -> Vec<FloatT> {
+    (0..size)
+        .map(|i| (0.5 - 0.5 * (2. * PI * i as f64 / size as f64).cos()) as FloatT)
+        .collect()
+}
+
+/// `Stft::inverse` leaves the samples whose summed squared window
+/// is below this unnormalized (the edges of the signal)
+const WINDOW_NORMALIZATION_EPSILON: FloatT = 1e-9;
+
+/// the short-time fourier transform of a signal
+#[derive(Clone, Debug)]
+pub struct Stft {
+    /// length of each frame. a power of 2.
+    pub frame_size: usize,
+    /// distance between the starts of two frames
+    pub hop_size: usize,
+    /// length of the transformed signal
+    pub signal_len: usize,
+    /// one row per frame. one column per frequency bin
+    /// from `0` to `frame_size / 2` inclusive.
+    pub spectra: Array2D<Complex<FloatT>>,
+}
+
+/// returns the short-time fourier transform of `signal`
+/// with a hann window. the last frame is padded with zeros.
+pub fn stft(signal: &[FloatT], frame_size: usize, hop_size: usize) -> Stft {
+    assert!(frame_size.is_power_of_two());
+    assert!(0 < hop_size && hop_size <= frame_size);
+
+    let nframes = if signal.len() <= frame_size {
+        1
+    } else {
+        1 + (signal.len() - frame_size + hop_size - 1) / hop_size
+    };
+    let nbins = frame_size / 2 + 1;
+    let window = hann(frame_size);
+
+    let mut spectra = Array2D::from_elem((nframes, nbins), Complex::new(0., 0.));
+    let mut frame = vec![Complex::new(0., 0.); frame_size];
+    for iframe in 0..nframes {
+        let start = iframe * hop_size;
+        for i in 0..frame_size {
+            let value = signal.get(start + i).cloned().unwrap_or(0.);
+            frame[i] = Complex::new(value * window[i], 0.);
+        }
+        fft(&mut frame[..], false);
+        for ibin in 0..nbins {
+            spectra[(iframe, ibin)] = frame[ibin];
+        }
+    }
+
+    Stft {
+        frame_size: frame_size,
+        hop_size: hop_size,
+        signal_len: signal.len(),
+        spectra: spectra,
+    }
+}
+
+impl Stft {
+    /// returns the magnitude spectrogram.
+    /// use it as `samples`: one frame per sample.
+    pub fn magnitudes(&self) -> Array2D<FloatT> {
+        let mut magnitudes = Array2D::<FloatT>::zeros(self.spectra.shape_as_tuple());
+        for (index, value) in self.spectra.indexed_iter() {
+            magnitudes[index] = value.norm();
+        }
+        magnitudes
+    }
+
+    /// returns the transform with each bin scaled by `mask`
+    pub fn masked(&self, mask: &Array2D<FloatT>) -> Stft {
+        assert_eq!(mask.shape_as_tuple(), self.spectra.shape_as_tuple());
+        let mut masked = self.clone();
+        for (index, value) in self.spectra.indexed_iter() {
+            masked.spectra[index] = Complex::new(value.re * mask[index], value.im * mask[index]);
+        }
+        masked
+    }
+
+    /// returns the signal by weighted overlap-add of the inverse transforms
+    /// of all frames. inverts `stft` up to rounding.
+    pub fn inverse(&self) -> Vec<FloatT> {
+        let (nframes, nbins) = self.spectra.shape_as_tuple();
+        let window = hann(self.frame_size);
+        let len = (nframes - 1) * self.hop_size + self.frame_size;
+        let mut signal = vec![FloatT::zero(); len];
+        let mut normalization = vec![FloatT::zero(); len];
+
+        let mut frame = vec![Complex::new(0., 0.); self.frame_size];
+        for iframe in 0..nframes {
+            // the spectrum of a real signal is conjugate symmetric
+            for ibin in 0..nbins {
+                frame[ibin] = self.spectra[(iframe, ibin)];
+            }
+            for ibin in nbins..self.frame_size {
+                frame[ibin] = self.spectra[(iframe, self.frame_size - ibin)].conj();
+            }
+            fft(&mut frame[..], true);
+
+            let start = iframe * self.hop_size;
+            for i in 0..self.frame_size {
+                signal[start + i] += frame[i].re * window[i];
+                normalization[start + i] += window[i] * window[i];
+            }
+        }
+
+        for (value, norm) in signal.iter_mut().zip(normalization.iter()) {
+            if WINDOW_NORMALIZATION_EPSILON < *norm {
+                *value /= *norm;
+            }
+        }
+        signal.truncate(self.signal_len);
+        signal
+    }
+}
+
+/// returns for each hidden variable of `nmf` a soft mask:
+/// the fraction `factor^power / sum(factors^power)`
+/// of each entry of the reconstruction the factor explains.
+/// the masks sum to `1`. `power` is usually `1` or `2`.
+pub fn wiener_masks(nmf: &NMFBlas, power: FloatT) -> Vec<Array2D<FloatT>> {
+    let mut masks: Vec<Array2D<FloatT>> = (0..nmf.nhidden())
+        .map(|ihidden| {
+            let mut mask = nmf.factor_reconstruction(ihidden);
+            for x in mask.iter_mut() {
+                *x = x.max(FloatT::zero()).powf(power);
+            }
+            mask
+        })
+        .collect();
+
+    let mut total = Array2D::<FloatT>::zeros(nmf.samples_shape());
+    for mask in masks.iter() {
+        for (index, value) in mask.indexed_iter() {
+            total[index] += *value;
+        }
+    }
+    for mask in masks.iter_mut() {
+        for (x, sum) in mask.iter_mut().zip(total.iter()) {
+            *x /= sum.max(DIVERGENCE_EPSILON);
+        }
+    }
+    masks
+}
+
+/// the result of `separate`
+pub struct Separation {
+    /// the factorization of the magnitude spectrogram
+    pub nmf: NMFBlas,
+    /// one signal per hidden variable. they sum to the input.
+    pub sources: Vec<Vec<FloatT>>,
+}
+
+/// separates `signal` into `nsources` sources.
+/// factorizes the magnitude spectrogram with `niterations` iterations
+/// minimizing `divergence` and resynthesizes each source
+/// with a wiener mask of `mask_power`.
+pub fn separate<R: Rng>(
+    signal: &[FloatT],
+    frame_size: usize,
+    hop_size: usize,
+    nsources: usize,
+    niterations: usize,
+    divergence: Divergence,
+    mask_power: FloatT,
+    rng: &mut R,
+) -> Separation {
+    let transform = stft(signal, frame_size, hop_size);
+    let magnitudes = transform.magnitudes();
+    let (nframes, nbins) = magnitudes.shape_as_tuple();
+
+    let mut nmf = NMFBlas::new_random01(nsources, nbins, nframes, rng);
+    for _ in 0..niterations {
+        nmf.iterate_divergence(&magnitudes, divergence);
+    }
+
+    let sources = wiener_masks(&nmf, mask_power)
+        .iter()
+        .map(|mask| transform.masked(mask).inverse())
+        .collect();
+
+    Separation {
+        nmf: nmf,
+        sources: sources,
+    }
+}
+
+/// writes each of `sources` to `{prefix}{index}.wav`
+pub fn save_sources(prefix: &str, sample_rate: u32, sources: &[Vec<FloatT>]) -> std::io::Result<()> {
+    for (isource, source) in sources.iter().enumerate() {
+        let audio = Audio::new_mono(sample_rate, source.clone());
+        try!(save_wav(format!("{}{}.wav", prefix, isource), &audio));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_fft_matches_dft() {
+    let input: Vec<Complex<FloatT>> = (0..8)
+        .map(|i| Complex::new((i as FloatT).sin(), (i * i) as FloatT / 10.))
+        .collect();
+    let mut output = input.clone();
+    fft(&mut output[..], false);
+    for k in 0..8 {
+        let mut expected = Complex::new(0., 0.);
+        for (i, x) in input.iter().enumerate() {
+            let angle = -2. * PI * (i * k) as f64 / 8.;
+            expected = expected + *x * Complex::new(angle.cos() as FloatT, angle.sin() as FloatT);
+        }
+        assert!((output[k] - expected).norm() < 1e-4);
+    }
+    fft(&mut output[..], true);
+    for (a, b) in output.iter().zip(input.iter()) {
+        assert!((*a - *b).norm() < 1e-5);
+    }
+}
+
+#[test]
+fn test_stft_inverse_reconstructs_signal() {
+    let signal: Vec<FloatT> = (0..1000).map(|i| (i as FloatT * 0.05).sin()).collect();
+    let reconstructed = stft(&signal[..], 64, 16).inverse();
+    assert_eq!(reconstructed.len(), signal.len());
+    // at the edges only few frames overlap with small window weights
+    for (a, b) in reconstructed.iter().zip(signal.iter()).skip(64).take(1000 - 2 * 64) {
+        assert!((a - b).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_wav_roundtrip() {
+    let audio = Audio {
+        sample_rate: 8000,
+        channels: vec![vec![0., 0.5, -0.5], vec![1., -1., 0.25]],
+    };
+    let mut bytes = Vec::new();
+    write_wav(&mut bytes, &audio).unwrap();
+    let read = read_wav(&mut &bytes[..]).unwrap();
+    assert_eq!(read.sample_rate, 8000);
+    for (read_channel, channel) in read.channels.iter().zip(audio.channels.iter()) {
+        for (a, b) in read_channel.iter().zip(channel.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+    assert_eq!(read.mono()[1], (read.channels[0][1] + read.channels[1][1]) / 2.);
+}
@@ -0,0 +1,24 @@
+//! shared by the binaries through `#[path]`
+//! such that the library does not depend on `clap`.
+
+use clap;
+
+/// returns an optional named argument `--name <value>`
+/// whose value has to be an unsigned integer.
+/// `name` is also used as the long flag and therefore
+/// has the lifetime of both.
+pub fn named_usize_arg<'n, 'h, 'g, 'p, 'r>(name: &'n str, help: &'h str) -> clap::Arg<'n, 'n, 'h, 'g, 'p, 'r> {
+    let error_message = format!(
+        "value provided for argument `{}` is not a valid unsigned integer number",
+        name);
+    clap::Arg::with_name(name)
+         .long(name)
+         .help(help)
+         .validator(move |val| {
+             match val.parse::<usize>() {
+                 Ok(_) => Ok(()),
+                 Err(_) => Err(error_message.clone())
+             }
+         })
+         .takes_value(true)
+}
@@ -2,6 +2,9 @@ use std::path::Path;
 
 #[macro_use]
 extern crate clap;
+#[path = "common/named_usize_arg.rs"]
+mod named_usize_arg;
+use named_usize_arg::named_usize_arg;
 
 extern crate num;
 use num::{Float, Zero};
@@ -11,46 +14,15 @@ extern crate image;
 extern crate rand;
 use rand::{StdRng, SeedableRng};
 
-extern crate rblas;
-use rblas::{Gemm, Matrix};
-use rblas::attribute::Transpose;
-
 extern crate ndarray;
 use ndarray::{Si, S};
-use ndarray::blas::{BlasArrayViewMut, AsBlas};
-
-extern crate nalgebra;
-use nalgebra::{DMat};
 
 extern crate onmf;
 use onmf::helpers::Array2D;
-use onmf::helpers::{ToImage, Normalize, magnify};
+use onmf::helpers::{ToImage, RgbMatrices, Normalize, magnify};
 
 use onmf::helpers::PartialMaxIteratorExt;
 
-// `name` and `long` have the same lifetime
-fn named_usize_arg<'n, 'h, 'g, 'p, 'r>(name: &'n str, help: &'h str) -> clap::Arg<'n, 'n, 'h, 'g, 'p, 'r> {
-    // we don't own `name` but we now own `error_message`
-    // which contains `name`.
-    let error_message = format!(
-        "value provided for argument `{}` is not a valid unsigned integer number",
-        name);
-    clap::Arg::with_name(name)
-         .long(name)
-         .help(help)
-         // `move` makes it such that the  closure owns `error_message`
-         .validator(move |val| {
-             match val.parse::<usize>() {
-                 Ok(_) => Ok(()),
-                 // the closure owns `error_message`.
-                 // we can't move `error_message` out of the closure.
-                 // we have to clone it instead.
-                 Err(_) => Err(error_message.clone())
-             }
-         })
-         .takes_value(true)
-}
-
 fn main() {
     let default_nhidden: usize = 10;
     let help_nhidden = format!("number of hidden (latent) variables to find (defaults to `{}`)", default_nhidden);
@@ -66,11 +38,15 @@ fn main() {
                  .help(&help_input_image_path)
                  .required(true))
             .arg(named_usize_arg("nhidden", &help_nhidden))
+            .arg(clap::Arg::with_name("rgb")
+                 .long("rgb")
+                 .help("factorize the stacked red, green and blue amounts instead of the grayscale amounts"))
             .get_matches();
 
     let nhidden: usize = value_t!(matches.value_of("nhidden"), usize).unwrap_or(default_nhidden);
     let input_image_path_string = matches.value_of("input-image-path").unwrap();
     let input_image_path = Path::new(input_image_path_string);
+    let rgb = matches.is_present("rgb");
 
     println!("nhidden = {}", nhidden);
     println!("input_image_path = {:?}", input_image_path);
@@ -83,25 +59,39 @@ fn main() {
 
     let image = image::open(&input_image_path).unwrap();
 
-    // to grayscale
-    let image_luma = image.to_luma();
+    // to grayscale or to red, green and blue
+    let image_rgb = image.to_rgb();
+    let nchannels: usize = if rgb { 3 } else { 1 };
 
-    println!("image_luma.dimensions() = {:?}", image_luma.dimensions());
-    let (width, height) = image_luma.dimensions();
+    println!("image_rgb.dimensions() = {:?}", image_rgb.dimensions());
+    let (width, height) = image_rgb.dimensions();
 
-    let nobserved = height as usize;
+    // the channels of each column are stacked on top of each other
+    let height = height as usize;
+    let nobserved = nchannels * height;
     let nsamples = width as usize;
     println!("nobserved = {}", nobserved);
     println!("nsamples = {}", nsamples);
 
-    // the indexing of image_array is reversed in respect to image_luma
+    // the indexing of image_array is reversed in respect to image_rgb
     let mut samples = Array2D::<f32>::zeros((nsamples, nobserved));
 
-    for (x, y, pixel) in image_luma.enumerate_pixels() {
-        let index = (x as usize, y as usize);
+    if rgb {
+        for (x, y, pixel) in image_rgb.enumerate_pixels() {
+            for ichannel in 0..nchannels {
+                let index = (x as usize, ichannel * height + y as usize);
+
+                // convert from 0-255 to 0.-1.
+                samples[index] = (pixel[ichannel] as f32) / 255.;
+            }
+        }
+    } else {
+        for (x, y, pixel) in image.to_luma().enumerate_pixels() {
+            let index = (x as usize, y as usize);
 
-        // convert from 0-255 to 0.-1.
-        samples[index] = (pixel[0] as f32) / 255.;
+            // convert from 0-255 to 0.-1.
+            samples[index] = (pixel[0] as f32) / 255.;
+        }
     }
 
     let seed: &[_] = &[1, 2, 3, 4];
@@ -110,8 +100,6 @@ fn main() {
     let mut nmf = onmf::NMFBlas::new_random01(
         nhidden, nobserved, nsamples, &mut rng);
 
-    let mut reconstruction = Array2D::<f32>::zeros(nmf.samples_shape());
-
     let thickness: usize = 20;
     let padding: usize = 20;
     let offset = padding * 2 + thickness;
@@ -126,34 +114,43 @@ fn main() {
         if iteration % 100 == 0 {
             println!("iteration = {:?} orthogonal = {:?}", iteration, orthogonal);
 
+            // makes `ihidden` in the filenames meaningful:
+            // the larger `ihidden` the smaller the contribution
+            nmf.canonicalize();
+
             // read testimage out of each row of nmf.hidden
             for ihidden in 0..nhidden {
                 let i = ihidden as isize;
-                let mut coefficients: Array2D<f32> =
+                let coefficients: Array2D<f32> =
                     nmf.weights
                         .slice(&[S, Si(i, Some(i + 1), 1)])
                        .to_owned();
-                let mut base: Array2D<f32> =
+                let base: Array2D<f32> =
                     nmf.hidden
                         .slice(&[Si(i, Some(i + 1), 1), S])
                         .to_owned();
-                Gemm::gemm(
-                    &1.,
-                    Transpose::NoTrans, &coefficients.blas(),
-                    Transpose::NoTrans, &base.blas(),
-                    &0.,
-                    &mut reconstruction.blas());
-
-                let mut image = DMat::<f32>::new_zeros(
-                    nobserved + offset,
-                    nsamples + offset);
+                let reconstruction = nmf.factor_reconstruction(ihidden);
+
+                let mut channels: Vec<Array2D<f32>> = (0..nchannels)
+                    .map(|_| Array2D::<f32>::zeros((height + offset, nsamples + offset)))
+                    .collect();
                 for ((row, col), val) in reconstruction.indexed_iter() {
-                    image[(col, row + offset)] = val.clone();
+                    channels[col / height][(col % height, row + offset)] = val.clone();
+                }
+                // normalize all channels together such that colors keep their hue
+                if rgb {
+                    let normalized = RgbMatrices {
+                        red: channels[0].clone(),
+                        green: channels[1].clone(),
+                        blue: channels[2].clone(),
+                    }.normalize();
+                    channels = vec![normalized.red, normalized.green, normalized.blue];
+                } else {
+                    channels[0] = channels[0].clone().normalize();
                 }
-                let mut image = image.normalize();
 
                 let coeffs_offset_col = offset;
-                let coeffs_offset_row = nobserved + padding;
+                let coeffs_offset_row = height + padding;
                 let max_coefficient = coefficients.iter().partial_max().unwrap();
                 for ((col, _), val) in coefficients.indexed_iter() {
                     for row in 0..thickness {
@@ -161,24 +158,38 @@ fn main() {
                             row + coeffs_offset_row,
                             col + coeffs_offset_col
                         );
-                        image[index] = val / max_coefficient;
+                        for image in channels.iter_mut() {
+                            image[index] = val / max_coefficient;
+                        }
                     }
                 }
 
                 let base_offset_col = padding;
                 let base_offset_row = 0;
                 let max_base = base.iter().partial_max().unwrap();
-                for ((_, row), val) in base.indexed_iter() {
+                for ((_, observed), val) in base.indexed_iter() {
+                    // each channel shows its own part of the base
+                    let row = observed % height;
                     for col in 0..thickness {
                         let index = (
                             row + base_offset_row,
                             col + base_offset_col
                         );
-                        image[index] = val / max_base;
+                        channels[observed / height][index] = val / max_base;
                     }
                 }
 
-                image.save_to_png(&format!("image-unmix-{}.png", ihidden)[..]).unwrap();
+                let filename = format!("image-unmix-{}.png", ihidden);
+                if rgb {
+                    let image = RgbMatrices {
+                        red: channels[0].clone(),
+                        green: channels[1].clone(),
+                        blue: channels[2].clone(),
+                    };
+                    image.save_to_png(&filename[..]).unwrap();
+                } else {
+                    channels[0].save_to_png(&filename[..]).unwrap();
+                }
             }
         }
 
@@ -0,0 +1,81 @@
+#[macro_use]
+extern crate clap;
+#[path = "common/named_usize_arg.rs"]
+mod named_usize_arg;
+use named_usize_arg::named_usize_arg;
+
+extern crate rand;
+use rand::{StdRng, SeedableRng};
+
+extern crate onmf;
+use onmf::helpers::{ToImage, Colormap, colormapped};
+use onmf::image_directory::{ImageSamples, save_hidden_images, save_hidden_rgb_images};
+
+fn main() {
+    let default_nhidden: usize = 49;
+    let help_nhidden = format!("number of hidden (latent) variables (parts) to find (defaults to `{}`)", default_nhidden);
+    let default_niterations: usize = 500;
+    let help_niterations = format!("number of iterations (defaults to `{}`)", default_niterations);
+    let default_magnification: usize = 4;
+    let help_magnification = format!("how much to magnify the saved parts (defaults to `{}`)", default_magnification);
+
+    let matches =
+        clap::App::new("image-directory-nmf")
+            .version(&crate_version!()[..])
+            .about("learns parts from the grayscale amounts of all same-size images in a directory")
+            .arg(clap::Arg::with_name("input-directory")
+                 .index(1)
+                 .help("directory containing the PGM or PNG images. one sample per image")
+                 .required(true))
+            .arg(named_usize_arg("nhidden", &help_nhidden))
+            .arg(named_usize_arg("niterations", &help_niterations))
+            .arg(named_usize_arg("magnification", &help_magnification))
+            .arg(clap::Arg::with_name("rgb")
+                 .long("rgb")
+                 .help("factorize the stacked red, green and blue amounts instead of the grayscale amounts"))
+            .get_matches();
+
+    let nhidden: usize = value_t!(matches.value_of("nhidden"), usize).unwrap_or(default_nhidden);
+    let niterations: usize = value_t!(matches.value_of("niterations"), usize).unwrap_or(default_niterations);
+    let magnification: usize = value_t!(matches.value_of("magnification"), usize).unwrap_or(default_magnification);
+    let input_directory = matches.value_of("input-directory").unwrap();
+    let rgb = matches.is_present("rgb");
+
+    let mut images = if rgb {
+        ImageSamples::load_directory_rgb(input_directory).unwrap()
+    } else {
+        ImageSamples::load_directory(input_directory).unwrap()
+    };
+    let (nsamples, nobserved) = (images.samples.shape()[0], images.samples.shape()[1]);
+    println!("nsamples = {}", nsamples);
+    println!("image_shape = {:?}", images.image_shape);
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut nmf = onmf::NMFBlas::new_random01(
+        nhidden, nobserved, nsamples, &mut rng);
+
+    for iteration in 0..niterations {
+        nmf.iterate(&mut images.samples, None);
+        if iteration % 100 == 0 {
+            println!("iteration = {} squared_error = {}",
+                     iteration, nmf.squared_error(&images.samples));
+        }
+    }
+
+    // makes `ihidden` in the filenames meaningful:
+    // the larger `ihidden` the smaller the contribution
+    nmf.canonicalize();
+
+    let prefix = "image-directory-part-";
+    if rgb {
+        save_hidden_rgb_images(&nmf.hidden, images.image_shape, magnification, prefix).unwrap();
+    } else {
+        save_hidden_images(&nmf.hidden, images.image_shape, magnification, prefix).unwrap();
+    }
+
+    // one row per image. one column per part.
+    colormapped(&nmf.weights, Colormap::Viridis)
+        .save_to_png("image-directory-weights.png").unwrap();
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+#[macro_use]
+extern crate clap;
+#[path = "common/named_usize_arg.rs"]
+mod named_usize_arg;
+use named_usize_arg::named_usize_arg;
+
+extern crate image;
+
+extern crate rand;
+use rand::{StdRng, SeedableRng};
+
+extern crate onmf;
+use onmf::helpers::{ToImage, magnify};
+use onmf::patches::{Patches, luma_to_array, dictionary_grid};
+
+fn main() {
+    let default_nhidden: usize = 16;
+    let help_nhidden = format!("number of patches in the dictionary (defaults to `{}`)", default_nhidden);
+    let default_patch_size: usize = 8;
+    let help_patch_size = format!("width and height of the patches (defaults to `{}`)", default_patch_size);
+    let default_stride: usize = 2;
+    let help_stride = format!("distance between neighbouring patches (defaults to `{}`)", default_stride);
+    let default_niterations: usize = 1000;
+    let help_niterations = format!("number of iterations (defaults to `{}`)", default_niterations);
+
+    let matches =
+        clap::App::new("image-patches-nmf")
+            .version(&crate_version!()[..])
+            .about("learns a dictionary of patches from the grayscale amounts of images")
+            .arg(clap::Arg::with_name("input-image-paths")
+                 .index(1)
+                 .multiple(true)
+                 .help("paths to the images to extract the patches from")
+                 .required(true))
+            .arg(named_usize_arg("nhidden", &help_nhidden))
+            .arg(named_usize_arg("patch-size", &help_patch_size))
+            .arg(named_usize_arg("stride", &help_stride))
+            .arg(named_usize_arg("niterations", &help_niterations))
+            .get_matches();
+
+    let nhidden: usize = value_t!(matches.value_of("nhidden"), usize).unwrap_or(default_nhidden);
+    let patch_size: usize = value_t!(matches.value_of("patch-size"), usize).unwrap_or(default_patch_size);
+    let stride: usize = value_t!(matches.value_of("stride"), usize).unwrap_or(default_stride);
+    let niterations: usize = value_t!(matches.value_of("niterations"), usize).unwrap_or(default_niterations);
+    let input_image_paths = matches.values_of("input-image-paths").unwrap();
+
+    let images: Vec<_> = input_image_paths.iter()
+        .map(|path| {
+            println!("reading {:?}", path);
+            luma_to_array(&image::open(&Path::new(path)).unwrap().to_luma())
+        })
+        .collect();
+
+    let mut patches = Patches::extract(&images[..], patch_size, stride);
+    println!("npatches = {}", patches.positions.len());
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut nmf = onmf::NMFBlas::new_random01(
+        nhidden, patch_size * patch_size, patches.positions.len(), &mut rng);
+
+    for iteration in 0..niterations {
+        nmf.iterate(&mut patches.samples, None);
+        if iteration % 100 == 0 {
+            println!("iteration = {} squared_error = {}",
+                     iteration, nmf.squared_error(&patches.samples));
+        }
+    }
+
+    // the most important patches come first in the grid
+    nmf.canonicalize();
+
+    let ncols = (nhidden as f64).sqrt().ceil() as usize;
+    let grid = dictionary_grid(&nmf.hidden, patch_size, ncols, 1);
+    magnify(grid, 4).save_to_png("image-patches-dictionary.png").unwrap();
+
+    let reconstruction = nmf.reconstruction();
+    for (iimage, image) in patches.reconstruct_images(&reconstruction).iter_mut().enumerate() {
+        for x in image.iter_mut() {
+            // reconstructions can slightly overshoot
+            *x = x.max(0.).min(1.);
+        }
+        image.save_to_png(&format!("image-patches-reconstruction-{}.png", iimage)[..]).unwrap();
+    }
+}
@@ -60,11 +60,20 @@ fn main() {
         if iteration % 10 == 0 {
             println!("iteration = {} alpha = {}", iteration, alpha);
 
-            // read testimage out of each row of nmf.hidden
+            // makes `ihidden` in the filenames meaningful:
+            // the larger `ihidden` the smaller the contribution.
+            // canonicalizes a copy because the orthogonality penalty
+            // is not scale invariant. rescaling the fitted model
+            // would change what it converges to.
+            let mut canonical = nmf.hidden.clone();
+            let mut canonical_weights = nmf.weights.clone();
+            onmf::canonicalize(&mut canonical, &mut canonical_weights);
+
+            // read testimage out of each row of the canonical hidden
             for irow in 0..nhidden {
                 let mut column = Vec::<FloatT>::new();
                 for icol in 0..nobserved {
-                    column.push(nmf.hidden[(irow, icol)]);
+                    column.push(canonical[(irow, icol)]);
                 }
                 let image = DMat::<FloatT>::from_col_vec(10, 10, &column[..]);
                 // println!("image {}", irow);
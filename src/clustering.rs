@@ -0,0 +1,202 @@
+/*!
+clusters the samples using the learned `weights`.
+
+each hidden variable is a cluster.
+a sample belongs to the clusters in proportion to its weights.
+
+also compares clusterings to known labels.
+*/
+
+use std::collections::BTreeMap;
+
+use num::Zero;
+
+use helpers::Array2D;
+use nmf_blas::{FloatT, ShapeAsTuple};
+use rank_selection::argmax_labels;
+
+/// returns for each sample (row of `weights`)
+/// the index of the hidden variable with the largest weight.
+/// ties go to the smaller index.
+/// the same as `rank_selection::argmax_labels`.
+pub fn hard_labels(weights: &Array2D<FloatT>) -> Vec<usize> {
+    assert!(0 < weights.shape_as_tuple().1);
+    argmax_labels(weights)
+}
+
+/// returns `weights` with each row scaled to sum up to `1`.
+/// entry `(i, j)` is the degree to which sample `i` belongs to cluster `j`.
+/// rows that are all zero stay zero.
+pub fn soft_memberships(weights: &Array2D<FloatT>) -> Array2D<FloatT> {
+    let (nsamples, nhidden) = weights.shape_as_tuple();
+    let mut memberships = weights.clone();
+    for isample in 0..nsamples {
+        let mut sum = FloatT::zero();
+        for ihidden in 0..nhidden {
+            sum += weights[(isample, ihidden)];
+        }
+        if FloatT::zero() == sum {
+            continue;
+        }
+        for ihidden in 0..nhidden {
+            memberships[(isample, ihidden)] /= sum;
+        }
+    }
+    memberships
+}
+
+/// returns the contingency table of two labelings.
+/// entry `[i][j]` counts the samples that have the `i`th distinct label
+/// in `a` and the `j`th distinct label in `b`.
+pub fn contingency(a: &[usize], b: &[usize]) -> Vec<Vec<usize>> {
+    assert_eq!(a.len(), b.len());
+
+    fn compact(labels: &[usize]) -> (Vec<usize>, usize) {
+        let mut indexes = BTreeMap::new();
+        for &label in labels.iter() {
+            let next = indexes.len();
+            indexes.entry(label).or_insert(next);
+        }
+        (labels.iter().map(|label| indexes[label]).collect(), indexes.len())
+    }
+
+    let (a, na) = compact(a);
+    let (b, nb) = compact(b);
+    let mut table = vec![vec![0; nb]; na];
+    for (&ia, &ib) in a.iter().zip(b.iter()) {
+        table[ia][ib] += 1;
+    }
+    table
+}
+
+/// fraction of samples that belong to the most frequent
+/// true class of their cluster.
+/// between `0` and `1`. higher is better.
+pub fn purity(labels: &[usize], truth: &[usize]) -> f64 {
+    assert!(!labels.is_empty());
+    let table = contingency(labels, truth);
+    let correct = table.iter()
+        .map(|row| row.iter().cloned().max().unwrap_or(0))
+        .fold(0, |sum, x| sum + x);
+    correct as f64 / labels.len() as f64
+}
+
+fn entropy(counts: &[usize], n: f64) -> f64 {
+    counts.iter()
+        .filter(|&&count| 0 < count)
+        .map(|&count| {
+            let p = count as f64 / n;
+            -p * p.ln()
+        })
+        .fold(0., |sum, x| sum + x)
+}
+
+/// normalized mutual information `I(labels, truth) / sqrt(H(labels) * H(truth))`.
+/// between `0` and `1`. higher is better.
+pub fn normalized_mutual_information(labels: &[usize], truth: &[usize]) -> f64 {
+    assert!(!labels.is_empty());
+    let n = labels.len() as f64;
+    let table = contingency(labels, truth);
+    let row_sums: Vec<usize> = table.iter()
+        .map(|row| row.iter().fold(0, |sum, x| sum + x))
+        .collect();
+    let col_sums: Vec<usize> = (0..table[0].len())
+        .map(|j| table.iter().fold(0, |sum, row| sum + row[j]))
+        .collect();
+
+    let mut mutual_information = 0.;
+    for (i, row) in table.iter().enumerate() {
+        for (j, &count) in row.iter().enumerate() {
+            if 0 < count {
+                let count = count as f64;
+                mutual_information += count / n
+                    * (n * count / (row_sums[i] as f64 * col_sums[j] as f64)).ln();
+            }
+        }
+    }
+
+    let entropy_labels = entropy(&row_sums[..], n);
+    let entropy_truth = entropy(&col_sums[..], n);
+    // both labelings put everything into one cluster
+    if 0. == entropy_labels && 0. == entropy_truth {
+        return 1.;
+    }
+    if 0. == entropy_labels || 0. == entropy_truth {
+        return 0.;
+    }
+    mutual_information / (entropy_labels * entropy_truth).sqrt()
+}
+
+fn pairs(n: usize) -> f64 {
+    (n * n.saturating_sub(1)) as f64 / 2.
+}
+
+/// adjusted rand index of `labels` and `truth`.
+/// `1` for identical clusterings. around `0` for random clusterings.
+/// `1` for a single sample which has no pairs to compare.
+pub fn adjusted_rand_index(labels: &[usize], truth: &[usize]) -> f64 {
+    assert!(!labels.is_empty());
+    if 1 == labels.len() {
+        return 1.;
+    }
+    let table = contingency(labels, truth);
+    let index = table.iter()
+        .flat_map(|row| row.iter())
+        .fold(0., |sum, &count| sum + pairs(count));
+    let row_pairs = table.iter()
+        .map(|row| pairs(row.iter().fold(0, |sum, x| sum + x)))
+        .fold(0., |sum, x| sum + x);
+    let col_pairs = (0..table[0].len())
+        .map(|j| pairs(table.iter().fold(0, |sum, row| sum + row[j])))
+        .fold(0., |sum, x| sum + x);
+
+    let expected_index = row_pairs * col_pairs / pairs(labels.len());
+    let max_index = (row_pairs + col_pairs) / 2.;
+    if max_index == expected_index {
+        return 1.;
+    }
+    (index - expected_index) / (max_index - expected_index)
+}
+
+#[test]
+fn test_hard_labels_and_soft_memberships() {
+    let mut weights = Array2D::<FloatT>::zeros((2, 3));
+    weights[(0, 2)] = 3.;
+    weights[(0, 0)] = 1.;
+    weights[(1, 1)] = 2.;
+
+    assert_eq!(hard_labels(&weights), vec![2, 1]);
+
+    // ties go to the smaller index like in `consensus_matrix`
+    weights[(1, 2)] = 2.;
+    assert_eq!(hard_labels(&weights), vec![2, 1]);
+
+    let memberships = soft_memberships(&weights);
+    assert_eq!(memberships[(0, 0)], 0.25);
+    assert_eq!(memberships[(0, 2)], 0.75);
+    assert_eq!(memberships[(1, 1)], 1.);
+}
+
+#[test]
+fn test_cluster_quality_of_renamed_labels() {
+    // the same clustering with different names is perfect
+    let labels = [5, 5, 3, 3, 7];
+    let truth = [0, 0, 1, 1, 2];
+    assert_eq!(purity(&labels, &truth), 1.);
+    assert!((normalized_mutual_information(&labels, &truth) - 1.).abs() < 1e-12);
+    assert!((adjusted_rand_index(&labels, &truth) - 1.).abs() < 1e-12);
+}
+
+#[test]
+fn test_cluster_quality_of_bad_clustering() {
+    let labels = [0, 1, 0, 1];
+    let truth = [0, 0, 1, 1];
+    assert_eq!(purity(&labels, &truth), 0.5);
+    assert!(normalized_mutual_information(&labels, &truth).abs() < 1e-12);
+    assert!(adjusted_rand_index(&labels, &truth) < 0.);
+}
+
+#[test]
+fn test_adjusted_rand_index_of_one_sample() {
+    assert_eq!(adjusted_rand_index(&[3], &[0]), 1.);
+}
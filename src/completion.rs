@@ -0,0 +1,159 @@
+/*!
+matrix completion on top of masked NMF.
+
+typical use case: a user-item rating matrix where most ratings are missing.
+one user per row (sample). one item per column (observed).
+
+the observed entries are split into a train and a test set.
+the NMF is fitted on the train entries only.
+the test entries are then used to measure how well
+the NMF predicts entries it has never seen.
+*/
+
+use rand::Rng;
+use num::{Float, Zero};
+
+use helpers::{Dims, Array2D};
+use mask::SparseMask;
+use nmf_blas::{NMFBlas, FloatT, ShapeAsTuple};
+
+/// error of the predictions on held out entries
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeldOutError {
+    /// root mean squared error
+    pub rmse: FloatT,
+    /// mean absolute error
+    pub mae: FloatT,
+    /// number of held out entries the errors were computed on
+    pub nentries: usize,
+}
+
+/// randomly splits the entries of `observed` into
+/// a train mask and a test mask.
+/// `test_fraction` of the entries (rounded down) end up in the test mask.
+pub fn split_observed<R: Rng>(
+    observed: &SparseMask,
+    test_fraction: f64,
+    rng: &mut R,
+) -> (SparseMask, SparseMask) {
+    assert!(0. <= test_fraction && test_fraction <= 1.);
+
+    // shuffle in the deterministic order of the entries
+    // such that the split only depends on `rng`
+    let mut entries: Vec<(Dims, FloatT)> = observed.entries.iter()
+        .map(|(&index, &weight)| (index, weight))
+        .collect();
+    rng.shuffle(&mut entries[..]);
+
+    let ntest = (entries.len() as f64 * test_fraction).floor() as usize;
+    let train_entries = entries.split_off(ntest);
+
+    let train = SparseMask {
+        shape: observed.shape,
+        entries: train_entries.into_iter().collect(),
+    };
+    let test = SparseMask {
+        shape: observed.shape,
+        entries: entries.into_iter().collect(),
+    };
+    (train, test)
+}
+
+/// returns the error of the predictions of `nmf`
+/// for the entries of `samples` that are observed in `test`.
+/// the weights of `test` are ignored.
+pub fn held_out_error(
+    nmf: &NMFBlas,
+    samples: &Array2D<FloatT>,
+    test: &SparseMask,
+) -> HeldOutError {
+    assert_eq!(samples.shape_as_tuple(), nmf.samples_shape());
+    assert_eq!(test.shape, nmf.samples_shape());
+
+    let mut squared_sum = FloatT::zero();
+    let mut absolute_sum = FloatT::zero();
+    for &(row, col) in test.entries.keys() {
+        let difference = nmf.predict(row, col) - samples[(row, col)];
+        squared_sum += difference * difference;
+        absolute_sum += difference.abs();
+    }
+
+    let nentries = test.nobserved_entries();
+    if 0 == nentries {
+        return HeldOutError {
+            rmse: FloatT::zero(),
+            mae: FloatT::zero(),
+            nentries: 0,
+        };
+    }
+    HeldOutError {
+        rmse: (squared_sum / nentries as FloatT).sqrt(),
+        mae: absolute_sum / nentries as FloatT,
+        nentries: nentries,
+    }
+}
+
+/// the result of `complete`
+pub struct Completion {
+    /// fitted on the `train` entries only
+    pub nmf: NMFBlas,
+    pub train: SparseMask,
+    pub test: SparseMask,
+    /// error on the `test` entries
+    pub error: HeldOutError,
+}
+
+/// splits the `observed` entries of `samples` into train and test entries,
+/// fits an NMF with `nhidden` hidden variables on the train entries
+/// for `niterations` iterations
+/// and reports the error on the test entries.
+/// use `completion.nmf.predict` to read out the missing entries.
+pub fn complete<R: Rng>(
+    samples: &mut Array2D<FloatT>,
+    observed: &SparseMask,
+    nhidden: usize,
+    niterations: usize,
+    test_fraction: f64,
+    rng: &mut R,
+) -> Completion {
+    let (nsamples, nobserved) = samples.shape_as_tuple();
+    assert_eq!(observed.shape, (nsamples, nobserved));
+
+    let (train, test) = split_observed(observed, test_fraction, rng);
+
+    let mut nmf = NMFBlas::new_random01(nhidden, nobserved, nsamples, rng);
+    for _ in 0..niterations {
+        nmf.iterate_masked(samples, &train, None);
+    }
+
+    let error = held_out_error(&nmf, samples, &test);
+
+    Completion {
+        nmf: nmf,
+        train: train,
+        test: test,
+        error: error,
+    }
+}
+
+#[test]
+fn test_split_observed() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut observed = SparseMask::new((4, 5));
+    for row in 0..4 {
+        for col in 0..5 {
+            observed.observe(row, col);
+        }
+    }
+
+    let (train, test) = split_observed(&observed, 0.25, &mut rng);
+    assert_eq!(train.nobserved_entries(), 15);
+    assert_eq!(test.nobserved_entries(), 5);
+    for index in test.entries.keys() {
+        assert!(!train.entries.contains_key(index));
+    }
+}
@@ -0,0 +1,294 @@
+/*!
+convolutive NMF (NMFD) for patterns that span several samples.
+
+samples are consecutive time frames (one per row).
+each hidden variable is a template of `nlags` consecutive frames
+instead of a single row.
+its activation (column of `weights`) is convolved in time with the template:
+
+`samples(t, :) ~ sum over lags l of weights(t - l, :) * hidden[l]`
+
+with `nlags = 1` this is ordinary NMF and `iterate`
+does the same updates as `NMFBlas::iterate`.
+see Smaragdis, "Non-negative Matrix Factor Deconvolution" (2004).
+*/
+
+use rand::Rng;
+use num::Zero;
+
+use helpers::{random01, Array2D};
+use nmf_blas::{
+    FloatT, ShapeAsTuple,
+    weights_multiplier, hidden_multiplier, reconstruction,
+    update_from_multiplier_and_divisor,
+};
+
+/// returns `input` with its rows moved down by `shift`.
+/// `output(t, :) = input(t - shift, :)`. the top rows are `0`.
+pub fn shift_down(input: &Array2D<FloatT>, shift: usize) -> Array2D<FloatT> {
+    let (nrows, ncols) = input.shape_as_tuple();
+    let mut output = Array2D::<FloatT>::zeros((nrows, ncols));
+    for row in shift..nrows {
+        for col in 0..ncols {
+            output[(row, col)] = input[(row - shift, col)];
+        }
+    }
+    output
+}
+
+/// returns `input` with its rows moved up by `shift`.
+/// `output(t, :) = input(t + shift, :)`. the bottom rows are `0`.
+pub fn shift_up(input: &Array2D<FloatT>, shift: usize) -> Array2D<FloatT> {
+    let (nrows, ncols) = input.shape_as_tuple();
+    let mut output = Array2D::<FloatT>::zeros((nrows, ncols));
+    for row in shift..nrows {
+        for col in 0..ncols {
+            output[(row - shift, col)] = input[(row, col)];
+        }
+    }
+    output
+}
+
+#[inline]
+fn add_assign(output: &mut Array2D<FloatT>, input: &Array2D<FloatT>) {
+    assert_eq!(output.shape_as_tuple(), input.shape_as_tuple());
+    for (x, y) in output.iter_mut().zip(input.iter()) {
+        *x += *y;
+    }
+}
+
+pub struct ConvolutiveNMF {
+    /// one `(nhidden, nobserved)` matrix per lag.
+    /// row `ihidden` of `hidden[lag]` is frame `lag`
+    /// of the template of hidden variable `ihidden`.
+    pub hidden: Vec<Array2D<FloatT>>,
+    /// `(nsamples, nhidden)`.
+    /// when each hidden variable's template starts.
+    pub weights: Array2D<FloatT>,
+}
+
+impl ConvolutiveNMF {
+    pub fn new_random01<R: Rng>(
+        nhidden: usize, nlags: usize, nobserved: usize, nsamples: usize, rng: &mut R) -> ConvolutiveNMF {
+        let hidden = (0..nlags)
+            .map(|_| {
+                let mut hidden = Array2D::<FloatT>::zeros((nhidden, nobserved));
+                for x in hidden.iter_mut() {
+                    *x = random01(rng);
+                }
+                hidden
+            })
+            .collect();
+
+        let mut weights = Array2D::<FloatT>::zeros((nsamples, nhidden));
+        for x in weights.iter_mut() {
+            *x = random01(rng);
+        }
+
+        Self::new(hidden, weights)
+    }
+
+    pub fn new(hidden: Vec<Array2D<FloatT>>, weights: Array2D<FloatT>) -> ConvolutiveNMF {
+        assert!(!hidden.is_empty(), "there must be at least one lag");
+        let hidden_shape = hidden[0].shape_as_tuple();
+        assert!(hidden.iter().all(|h| h.shape_as_tuple() == hidden_shape),
+                "the hidden matrices of all lags must have the same shape");
+        assert!(weights.shape()[1] == hidden_shape.0,
+                "row count of hidden must be equal to column count of weights");
+        ConvolutiveNMF {
+            hidden: hidden,
+            weights: weights,
+        }
+    }
+
+    /// returns the number of frames of each template
+    #[inline]
+    pub fn nlags(&self) -> usize {
+        self.hidden.len()
+    }
+
+    /// returns the number of hidden variables
+    #[inline]
+    pub fn nhidden(&self) -> usize {
+        self.weights.shape()[1]
+    }
+
+    /// returns the number of observed variables
+    #[inline]
+    pub fn nobserved(&self) -> usize {
+        self.hidden[0].shape()[1]
+    }
+
+    /// returns the number of samples (time frames)
+    #[inline]
+    pub fn nsamples(&self) -> usize {
+        self.weights.shape()[0]
+    }
+
+    pub fn samples_shape(&self) -> (usize, usize) {
+        (self.nsamples(), self.nobserved())
+    }
+
+    /// returns the `(nlags, nobserved)` template of hidden variable `ihidden`
+    pub fn template(&self, ihidden: usize) -> Array2D<FloatT> {
+        assert!(ihidden < self.nhidden());
+        let mut template = Array2D::<FloatT>::zeros((self.nlags(), self.nobserved()));
+        for (lag, hidden) in self.hidden.iter().enumerate() {
+            for iobserved in 0..self.nobserved() {
+                template[(lag, iobserved)] = hidden[(ihidden, iobserved)];
+            }
+        }
+        template
+    }
+
+    /// returns `sum over lags l of shift_down(weights, l) * hidden[l]`
+    pub fn reconstruction(&self) -> Array2D<FloatT> {
+        let mut result = Array2D::<FloatT>::zeros(self.samples_shape());
+        let mut tmp = Array2D::<FloatT>::zeros(self.samples_shape());
+        for (lag, hidden) in self.hidden.iter().enumerate() {
+            let mut shifted_weights = shift_down(&self.weights, lag);
+            // gemm needs mutable access
+            let mut hidden = hidden.clone();
+            reconstruction(&mut shifted_weights, &mut hidden, &mut tmp);
+            add_assign(&mut result, &tmp);
+        }
+        result
+    }
+
+    /// returns the part of the reconstruction
+    /// that hidden variable `ihidden` contributes
+    pub fn factor_reconstruction(&self, ihidden: usize) -> Array2D<FloatT> {
+        assert!(ihidden < self.nhidden());
+        let (nsamples, nobserved) = self.samples_shape();
+        let mut result = Array2D::<FloatT>::zeros((nsamples, nobserved));
+        for (lag, hidden) in self.hidden.iter().enumerate() {
+            for isample in lag..nsamples {
+                let weight = self.weights[(isample - lag, ihidden)];
+                for iobserved in 0..nobserved {
+                    result[(isample, iobserved)] += weight * hidden[(ihidden, iobserved)];
+                }
+            }
+        }
+        result
+    }
+
+    /// returns the objective `sum((samples - reconstruction)^2)`
+    /// that `iterate` minimizes. lower is better.
+    pub fn squared_error(&self, samples: &Array2D<FloatT>) -> FloatT {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+        let reconstruction = self.reconstruction();
+        let mut sum = FloatT::zero();
+        for (x, y) in samples.iter().zip(reconstruction.iter()) {
+            sum += (x - y) * (x - y);
+        }
+        sum
+    }
+
+    /// does one iteration step.
+    /// all updates are computed from the current reconstruction
+    /// before any of `weights` and `hidden` change.
+    pub fn iterate(&mut self, samples: &mut Array2D<FloatT>) {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+        let weights_shape = self.weights.shape_as_tuple();
+        let hidden_shape = self.hidden[0].shape_as_tuple();
+
+        let mut reconstruction = self.reconstruction();
+
+        // TODO keep these around between iterations to prevent the allocations
+        let mut weights_multiplier_sum = Array2D::<FloatT>::zeros(weights_shape);
+        let mut weights_divisor_sum = Array2D::<FloatT>::zeros(weights_shape);
+        let mut tmp = Array2D::<FloatT>::zeros(weights_shape);
+        let mut hidden_multipliers = Vec::with_capacity(self.nlags());
+        let mut hidden_divisors = Vec::with_capacity(self.nlags());
+
+        for lag in 0..self.nlags() {
+            // weights_multiplier_sum += shift_up(samples, lag) * hidden[lag].transpose()
+            let mut shifted = shift_up(samples, lag);
+            weights_multiplier(&mut shifted, &mut self.hidden[lag], &mut tmp);
+            add_assign(&mut weights_multiplier_sum, &tmp);
+
+            // weights_divisor_sum += shift_up(reconstruction, lag) * hidden[lag].transpose()
+            let mut shifted = shift_up(&reconstruction, lag);
+            weights_multiplier(&mut shifted, &mut self.hidden[lag], &mut tmp);
+            add_assign(&mut weights_divisor_sum, &tmp);
+
+            let mut shifted_weights = shift_down(&self.weights, lag);
+
+            // hidden_multiplier <- shift_down(weights, lag).transpose() * samples
+            let mut multiplier = Array2D::<FloatT>::zeros(hidden_shape);
+            hidden_multiplier(&mut shifted_weights, samples, &mut multiplier);
+            hidden_multipliers.push(multiplier);
+
+            // hidden_divisor <- shift_down(weights, lag).transpose() * reconstruction
+            let mut divisor = Array2D::<FloatT>::zeros(hidden_shape);
+            hidden_multiplier(&mut shifted_weights, &mut reconstruction, &mut divisor);
+            hidden_divisors.push(divisor);
+        }
+
+        update_from_multiplier_and_divisor(
+            &weights_multiplier_sum,
+            &weights_divisor_sum,
+            &mut self.weights);
+
+        for (lag, hidden) in self.hidden.iter_mut().enumerate() {
+            update_from_multiplier_and_divisor(
+                &hidden_multipliers[lag],
+                &hidden_divisors[lag],
+                hidden);
+        }
+    }
+}
+
+#[test]
+fn test_single_lag_equals_nmf_blas() {
+    use rand::{StdRng, SeedableRng};
+    use nmf_blas::NMFBlas;
+
+    let mut rng: StdRng = SeedableRng::from_seed(&[4, 5, 6][..]);
+    let mut samples = Array2D::<FloatT>::zeros((6, 4));
+    for x in samples.iter_mut() {
+        *x = random01(&mut rng);
+    }
+    let mut convolutive = ConvolutiveNMF::new_random01(2, 1, 4, 6, &mut rng);
+    let mut nmf = NMFBlas::new(convolutive.hidden[0].clone(), convolutive.weights.clone());
+
+    for _ in 0..5 {
+        convolutive.iterate(&mut samples);
+        nmf.iterate(&mut samples, None);
+    }
+    for (a, b) in convolutive.hidden[0].iter().zip(nmf.hidden.iter()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+    for (a, b) in convolutive.weights.iter().zip(nmf.weights.iter()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_learns_time_extended_pattern() {
+    use rand::{StdRng, SeedableRng};
+
+    // a diagonal stripe spanning 3 frames starts at frames 0, 5 and 10
+    let mut truth_weights = Array2D::<FloatT>::zeros((15, 1));
+    for &start in [0, 5, 10].iter() {
+        truth_weights[(start, 0)] = 1.;
+    }
+    let truth_hidden = (0..3)
+        .map(|lag| {
+            let mut hidden = Array2D::<FloatT>::zeros((1, 3));
+            hidden[(0, lag)] = 1.;
+            hidden
+        })
+        .collect();
+    let mut samples = ConvolutiveNMF::new(truth_hidden, truth_weights).reconstruction();
+
+    let mut rng: StdRng = SeedableRng::from_seed(&[7][..]);
+    let mut nmf = ConvolutiveNMF::new_random01(1, 3, 3, 15, &mut rng);
+    let before = nmf.squared_error(&samples);
+    for _ in 0..500 {
+        nmf.iterate(&mut samples);
+    }
+    let after = nmf.squared_error(&samples);
+    assert!(after < 0.01 * before);
+    assert_eq!(nmf.template(0).shape_as_tuple(), (3, 3));
+}
@@ -0,0 +1,427 @@
+/*!
+read samples from and write matrices to delimited text (CSV, TSV).
+
+one sample per line. one observed variable per column.
+optionally the first line holds the names of the observed variables
+and the first column holds the ids of the samples.
+the names are kept in `Labels` alongside the model
+such that `hidden` and `weights` can be written back out with them
+and factors can be reported in terms of named observed variables.
+
+empty fields and `NA`, `NaN` and `nan` are read as `NaN`.
+use `mask::mask_from_finite` to treat them as missing.
+fields may be quoted with `"`. a `""` inside a quoted field is a `"`.
+*/
+
+use std;
+use std::io::{Read, Write, BufRead, BufReader};
+use std::fs::File;
+use std::path::Path;
+
+use num::Float;
+
+use helpers::{Array2D, descending_nan_last};
+use nmf_blas::{NMFBlas, FloatT, ShapeAsTuple};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum DelimitedError {
+        /// an error has occured when reading or writing the file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// a field could not be parsed as a number
+        Parse(line: usize, field: String) {
+            display("line {}: can't parse `{}` as a number", line, field)
+        }
+        /// a line has a different number of fields than the first line
+        Ragged(line: usize, found: usize, expected: usize) {
+            display("line {}: found {} fields but expected {}", line, found, expected)
+        }
+        /// there are no samples
+        Empty {}
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DelimitedOptions {
+    pub delimiter: char,
+    /// the first line holds the names of the observed variables
+    pub has_header: bool,
+    /// the first column holds the ids of the samples
+    pub has_sample_ids: bool,
+}
+
+impl DelimitedOptions {
+    pub fn csv() -> DelimitedOptions {
+        DelimitedOptions {
+            delimiter: ',',
+            has_header: true,
+            has_sample_ids: false,
+        }
+    }
+
+    pub fn tsv() -> DelimitedOptions {
+        DelimitedOptions {
+            delimiter: '\t',
+            ..DelimitedOptions::csv()
+        }
+    }
+
+    /// TSV for `.tsv` and `.tab` files. CSV otherwise.
+    pub fn for_path<P: AsRef<Path>>(path: P) -> DelimitedOptions {
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("tsv") | Some("tab") => DelimitedOptions::tsv(),
+            _ => DelimitedOptions::csv(),
+        }
+    }
+}
+
+/// names of the observed variables and ids of the samples
+#[derive(Clone, Debug, PartialEq)]
+pub struct Labels {
+    /// one per column of `samples` and `hidden`
+    pub observed: Vec<String>,
+    /// one per row of `samples` and `weights`
+    pub samples: Vec<String>,
+}
+
+impl Labels {
+    /// returns the labels `observed-0, observed-1, ...` and `sample-0, sample-1, ...`
+    pub fn numbered(nobserved: usize, nsamples: usize) -> Labels {
+        Labels {
+            observed: (0..nobserved).map(|i| format!("observed-{}", i)).collect(),
+            samples: (0..nsamples).map(|i| format!("sample-{}", i)).collect(),
+        }
+    }
+
+    /// returns the labels `hidden-0, hidden-1, ...` for the hidden variables
+    pub fn hidden(nhidden: usize) -> Vec<String> {
+        (0..nhidden).map(|i| format!("hidden-{}", i)).collect()
+    }
+}
+
+/// samples read from delimited text together with their labels
+pub struct LabeledSamples {
+    pub samples: Array2D<FloatT>,
+    /// generated by `Labels::numbered` where the text had no labels
+    pub labels: Labels,
+}
+
+/// splits `line` into fields at `delimiter` respecting `"` quotes
+pub fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if '"' == c {
+                if Some(&'"') == chars.peek() {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if '"' == c {
+            quoted = true;
+        } else if delimiter == c {
+            fields.push(field);
+            field = String::new();
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// returns `field` quoted if it contains the `delimiter`, a quote or a newline
+pub fn quote_field(field: &str, delimiter: char) -> String {
+    if field.contains(delimiter) || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace("\"", "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse_value(field: &str, line: usize) -> Result<FloatT, DelimitedError> {
+    let field = field.trim();
+    match field {
+        "" | "NA" | "NaN" | "nan" => Ok(FloatT::nan()),
+        _ => field.parse::<FloatT>()
+            .map_err(|_| DelimitedError::Parse(line, field.to_string())),
+    }
+}
+
+/// reads samples from delimited text
+pub fn read_samples<R: Read>(reader: R, options: DelimitedOptions) -> Result<LabeledSamples, DelimitedError> {
+    let reader = BufReader::new(reader);
+    let mut header: Option<Vec<String>> = None;
+    let mut sample_ids = Vec::new();
+    let mut rows: Vec<Vec<FloatT>> = Vec::new();
+    let mut nfields = None;
+
+    for (iline, line) in reader.lines().enumerate() {
+        let line = try!(line);
+        // line numbers start at 1
+        let line_number = iline + 1;
+        let line = line.trim_right_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = split_fields(line, options.delimiter);
+
+        match nfields {
+            None => nfields = Some(fields.len()),
+            Some(expected) if expected != fields.len() =>
+                return Err(DelimitedError::Ragged(line_number, fields.len(), expected)),
+            _ => {}
+        }
+
+        if options.has_header && header.is_none() {
+            if options.has_sample_ids {
+                fields.remove(0);
+            }
+            header = Some(fields.into_iter().map(|field| field.trim().to_string()).collect());
+            continue;
+        }
+
+        if options.has_sample_ids {
+            sample_ids.push(fields.remove(0).trim().to_string());
+        }
+        let mut row = Vec::with_capacity(fields.len());
+        for field in fields.iter() {
+            row.push(try!(parse_value(&field[..], line_number)));
+        }
+        rows.push(row);
+    }
+
+    if rows.is_empty() {
+        return Err(DelimitedError::Empty);
+    }
+    let nsamples = rows.len();
+    let nobserved = rows[0].len();
+
+    let mut samples = Array2D::<FloatT>::zeros((nsamples, nobserved));
+    for (isample, row) in rows.iter().enumerate() {
+        for (iobserved, value) in row.iter().enumerate() {
+            samples[(isample, iobserved)] = *value;
+        }
+    }
+
+    let mut labels = Labels::numbered(nobserved, nsamples);
+    if let Some(header) = header {
+        labels.observed = header;
+    }
+    if options.has_sample_ids {
+        labels.samples = sample_ids;
+    }
+
+    Ok(LabeledSamples {
+        samples: samples,
+        labels: labels,
+    })
+}
+
+pub fn load_samples<P: AsRef<Path>>(path: P, options: DelimitedOptions) -> Result<LabeledSamples, DelimitedError> {
+    let file = try!(File::open(path));
+    read_samples(file, options)
+}
+
+/// writes `matrix` as delimited text.
+/// the first line holds `col_labels`.
+/// the first column holds `row_labels`.
+/// the field above the row labels holds `corner`.
+pub fn write_matrix<W: Write>(
+    writer: &mut W,
+    matrix: &Array2D<FloatT>,
+    row_labels: &[String],
+    col_labels: &[String],
+    corner: &str,
+    delimiter: char,
+) -> std::io::Result<()> {
+    let (nrows, ncols) = matrix.shape_as_tuple();
+    assert_eq!(nrows, row_labels.len());
+    assert_eq!(ncols, col_labels.len());
+
+    try!(write!(writer, "{}", quote_field(corner, delimiter)));
+    for label in col_labels.iter() {
+        try!(write!(writer, "{}{}", delimiter, quote_field(&label[..], delimiter)));
+    }
+    try!(writeln!(writer, ""));
+
+    for row in 0..nrows {
+        try!(write!(writer, "{}", quote_field(&row_labels[row][..], delimiter)));
+        for col in 0..ncols {
+            try!(write!(writer, "{}{}", delimiter, matrix[(row, col)]));
+        }
+        try!(writeln!(writer, ""));
+    }
+    Ok(())
+}
+
+/// writes `hidden` with one line per hidden variable
+/// and one column per observed variable named by `labels`
+pub fn write_hidden<W: Write>(
+    writer: &mut W, hidden: &Array2D<FloatT>, labels: &Labels, delimiter: char,
+) -> std::io::Result<()> {
+    let row_labels = Labels::hidden(hidden.shape()[0]);
+    write_matrix(writer, hidden, &row_labels[..], &labels.observed[..], "hidden", delimiter)
+}
+
+/// writes `weights` with one line per sample named by `labels`
+/// and one column per hidden variable
+pub fn write_weights<W: Write>(
+    writer: &mut W, weights: &Array2D<FloatT>, labels: &Labels, delimiter: char,
+) -> std::io::Result<()> {
+    let col_labels = Labels::hidden(weights.shape()[1]);
+    write_matrix(writer, weights, &labels.samples[..], &col_labels[..], "sample", delimiter)
+}
+
+/// returns for each hidden variable (row of `hidden`)
+/// the `n` observed variables with the largest values in descending order.
+/// these dominate the hidden variable.
+/// `NaN` values come last.
+pub fn dominant_observed(hidden: &Array2D<FloatT>, observed: &[String], n: usize) -> Vec<Vec<(String, FloatT)>> {
+    let (nhidden, nobserved) = hidden.shape_as_tuple();
+    assert_eq!(nobserved, observed.len());
+    (0..nhidden).map(|ihidden| {
+        let mut values: Vec<(usize, FloatT)> = (0..nobserved)
+            .map(|iobserved| (iobserved, hidden[(ihidden, iobserved)]))
+            .collect();
+        values.sort_by(|a, b| descending_nan_last(&a.1, &b.1));
+        values.iter()
+            .take(n)
+            .map(|&(iobserved, value)| (observed[iobserved].clone(), value))
+            .collect()
+    }).collect()
+}
+
+/// a model together with the labels of the samples it was fitted to
+pub struct LabeledNMF {
+    pub nmf: NMFBlas,
+    pub labels: Labels,
+}
+
+impl LabeledNMF {
+    pub fn new(nmf: NMFBlas, labels: Labels) -> LabeledNMF {
+        assert_eq!(nmf.hidden.shape()[1], labels.observed.len());
+        assert_eq!(nmf.weights.shape()[0], labels.samples.len());
+        LabeledNMF {
+            nmf: nmf,
+            labels: labels,
+        }
+    }
+
+    pub fn write_hidden<W: Write>(&self, writer: &mut W, delimiter: char) -> std::io::Result<()> {
+        write_hidden(writer, &self.nmf.hidden, &self.labels, delimiter)
+    }
+
+    pub fn write_weights<W: Write>(&self, writer: &mut W, delimiter: char) -> std::io::Result<()> {
+        write_weights(writer, &self.nmf.weights, &self.labels, delimiter)
+    }
+
+    /// saves `hidden` and `weights` to two files.
+    /// the delimiter is chosen from the extension of each path.
+    pub fn save<P: AsRef<Path>, Q: AsRef<Path>>(&self, hidden_path: P, weights_path: Q) -> std::io::Result<()> {
+        let delimiter = DelimitedOptions::for_path(hidden_path.as_ref()).delimiter;
+        let mut file = try!(File::create(hidden_path));
+        try!(self.write_hidden(&mut file, delimiter));
+        let delimiter = DelimitedOptions::for_path(weights_path.as_ref()).delimiter;
+        let mut file = try!(File::create(weights_path));
+        self.write_weights(&mut file, delimiter)
+    }
+
+    /// returns for each hidden variable the `n` dominating observed variables
+    pub fn dominant_observed(&self, n: usize) -> Vec<Vec<(String, FloatT)>> {
+        dominant_observed(&self.nmf.hidden, &self.labels.observed[..], n)
+    }
+
+    /// returns a human readable report with one line per hidden variable
+    /// listing its `n` dominating observed variables
+    pub fn factor_report(&self, n: usize) -> String {
+        let mut report = String::new();
+        for (ihidden, dominant) in self.dominant_observed(n).iter().enumerate() {
+            let names: Vec<String> = dominant.iter()
+                .map(|&(ref name, value)| format!("{} ({})", name, value))
+                .collect();
+            report.push_str(&format!("hidden-{}: {}\n", ihidden, names.join(", "))[..]);
+        }
+        report
+    }
+}
+
+#[test]
+fn test_split_fields_with_quotes() {
+    assert_eq!(split_fields("a,\"b,c\",\"d\"\"e\",", ','),
+               vec!["a", "b,c", "d\"e", ""]);
+    assert_eq!(quote_field("b,c", ','), "\"b,c\"");
+    assert_eq!(quote_field("d\"e", ','), "\"d\"\"e\"");
+}
+
+#[test]
+fn test_read_samples_with_labels() {
+    let text = "id,x,y\nfirst,1,2\nsecond,,4.5\n";
+    let options = DelimitedOptions { has_sample_ids: true, ..DelimitedOptions::csv() };
+    let labeled = read_samples(text.as_bytes(), options).unwrap();
+    assert_eq!(labeled.labels.observed, vec!["x", "y"]);
+    assert_eq!(labeled.labels.samples, vec!["first", "second"]);
+    assert_eq!(labeled.samples[(0, 1)], 2.);
+    assert!(labeled.samples[(1, 0)].is_nan());
+    assert_eq!(labeled.samples[(1, 1)], 4.5);
+
+    let mut bytes = Vec::new();
+    write_weights(&mut bytes, &labeled.samples, &Labels {
+        observed: Vec::new(),
+        samples: labeled.labels.samples.clone(),
+    }, '\t').unwrap();
+    assert!(String::from_utf8(bytes).unwrap().starts_with("sample\thidden-0\thidden-1\nfirst\t1\t2\n"));
+}
+
+#[test]
+fn test_ragged_rows() {
+    let text = "1,2\n3\n";
+    let options = DelimitedOptions { has_header: false, ..DelimitedOptions::csv() };
+    match read_samples(text.as_bytes(), options) {
+        Err(DelimitedError::Ragged(2, 1, 2)) => {}
+        other => panic!("expected a ragged error but got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_dominant_observed() {
+    let mut hidden = Array2D::<FloatT>::zeros((1, 3));
+    hidden[(0, 0)] = 1.;
+    hidden[(0, 2)] = 3.;
+    let observed = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    assert_eq!(dominant_observed(&hidden, &observed[..], 2),
+               vec![vec![("c".to_string(), 3.), ("a".to_string(), 1.)]]);
+}
+
+#[test]
+fn test_dominant_observed_with_nan() {
+    let mut hidden = Array2D::<FloatT>::zeros((1, 3));
+    hidden[(0, 0)] = FloatT::nan();
+    hidden[(0, 2)] = 3.;
+    let observed = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    assert_eq!(dominant_observed(&hidden, &observed[..], 2),
+               vec![vec![("c".to_string(), 3.), ("b".to_string(), 0.)]]);
+}
+
+#[test]
+fn test_factor_report() {
+    let mut hidden = Array2D::<FloatT>::zeros((2, 2));
+    hidden[(0, 1)] = 2.;
+    hidden[(1, 0)] = 1.;
+    let weights = Array2D::<FloatT>::zeros((1, 2));
+    let labels = Labels {
+        observed: vec!["x".to_string(), "y".to_string()],
+        samples: vec!["first".to_string()],
+    };
+    let labeled = LabeledNMF::new(NMFBlas::new(hidden, weights), labels);
+    assert_eq!(labeled.factor_report(1), "hidden-0: y (2)\nhidden-1: x (1)\n");
+}
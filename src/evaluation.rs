@@ -0,0 +1,223 @@
+/*!
+checks how well a factorization recovered known factors.
+
+the learned factors (rows of `hidden`) are matched one to one
+to the ground truth factors such that the summed cosine similarity
+of the matched pairs is maximal (hungarian assignment).
+*/
+
+use std::f64;
+
+use nalgebra::DMat;
+use num::traits::ToPrimitive;
+
+use helpers::Array2D;
+use nmf_blas::{FloatT, ShapeAsTuple};
+
+/// returns the cosine similarity of row `ia` of `a` and row `ib` of `b`.
+/// `0` if one of the rows is zero.
+pub fn cosine_similarity(a: &Array2D<FloatT>, ia: usize, b: &Array2D<FloatT>, ib: usize) -> f64 {
+    let ncols = a.shape()[1];
+    assert_eq!(ncols, b.shape()[1]);
+    let mut dot = 0.;
+    let mut norm_a = 0.;
+    let mut norm_b = 0.;
+    for col in 0..ncols {
+        let x = a[(ia, col)] as f64;
+        let y = b[(ib, col)] as f64;
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if 0. == norm_a || 0. == norm_b {
+        return 0.;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// solves the assignment problem for the square matrix `cost`.
+/// returns for each row the column assigned to it
+/// such that the summed cost is minimal.
+pub fn hungarian(cost: &Vec<Vec<f64>>) -> Vec<usize> {
+    let n = cost.len();
+    for row in cost.iter() {
+        assert_eq!(row.len(), n);
+    }
+
+    // potentials and matching use 1-based indexes.
+    // index 0 is a virtual column used during augmentation.
+    let mut u = vec![0.; n + 1];
+    let mut v = vec![0.; n + 1];
+    // row_of_col[j] is the row matched to column j
+    let mut row_of_col = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+
+    for i in 1..(n + 1) {
+        row_of_col[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = row_of_col[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..(n + 1) {
+                if !used[j] {
+                    let current = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                    if current < minv[j] {
+                        minv[j] = current;
+                        way[j] = j0;
+                    }
+                    if minv[j] < delta {
+                        delta = minv[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..(n + 1) {
+                if used[j] {
+                    u[row_of_col[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if 0 == row_of_col[j0] {
+                break;
+            }
+        }
+        // augment along the path
+        loop {
+            let j1 = way[j0];
+            row_of_col[j0] = row_of_col[j1];
+            j0 = j1;
+            if 0 == j0 {
+                break;
+            }
+        }
+    }
+
+    let mut col_of_row = vec![0; n];
+    for j in 1..(n + 1) {
+        if 0 < row_of_col[j] {
+            col_of_row[row_of_col[j] - 1] = j - 1;
+        }
+    }
+    col_of_row
+}
+
+/// a ground truth factor and the learned factor it was matched to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FactorMatch {
+    /// row in the ground truth factors
+    pub itruth: usize,
+    /// row in `hidden`. `None` if there are fewer learned factors than
+    /// ground truth factors and this one was left over.
+    pub ilearned: Option<usize>,
+    /// cosine similarity of the pair. `0` if `ilearned` is `None`.
+    pub similarity: f64,
+}
+
+/// the result of `match_factors`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactorRecovery {
+    /// one match per ground truth factor in order
+    pub matches: Vec<FactorMatch>,
+    /// mean similarity over all ground truth factors.
+    /// between `0` and `1`. `1` if all factors were recovered exactly.
+    pub recovery_score: f64,
+}
+
+/// matches the rows of `learned` (typically `hidden`)
+/// to the rows of `truth` one to one
+/// maximizing the summed cosine similarity.
+/// learned factors that are not needed are ignored.
+pub fn match_factors(learned: &Array2D<FloatT>, truth: &Array2D<FloatT>) -> FactorRecovery {
+    let (nlearned, nobserved) = learned.shape_as_tuple();
+    let ntruth = truth.shape()[0];
+    assert_eq!(nobserved, truth.shape()[1]);
+    assert!(0 < ntruth);
+
+    // pad to a square matrix. padding costs nothing.
+    let size = if nlearned < ntruth { ntruth } else { nlearned };
+    let mut cost = vec![vec![0.; size]; size];
+    for itruth in 0..ntruth {
+        for ilearned in 0..nlearned {
+            cost[itruth][ilearned] = -cosine_similarity(truth, itruth, learned, ilearned);
+        }
+    }
+
+    let assignment = hungarian(&cost);
+
+    let matches: Vec<FactorMatch> = (0..ntruth).map(|itruth| {
+        let ilearned = assignment[itruth];
+        if ilearned < nlearned {
+            FactorMatch {
+                itruth: itruth,
+                ilearned: Some(ilearned),
+                similarity: -cost[itruth][ilearned],
+            }
+        } else {
+            FactorMatch {
+                itruth: itruth,
+                ilearned: None,
+                similarity: 0.,
+            }
+        }
+    }).collect();
+
+    let recovery_score = matches.iter().fold(0., |sum, m| sum + m.similarity)
+        / ntruth as f64;
+
+    FactorRecovery {
+        matches: matches,
+        recovery_score: recovery_score,
+    }
+}
+
+/// returns a matrix with one of `factors` flattened into each row.
+/// flattens in the same (column major) order as `DMat::as_vec`
+/// which is how `testimage_generator` images are fed into the NMF.
+pub fn factors_to_rows<T: ToPrimitive + Clone>(factors: &[DMat<T>]) -> Array2D<FloatT> {
+    assert!(!factors.is_empty());
+    let nobserved = factors[0].as_vec().len();
+    let mut rows = Array2D::<FloatT>::zeros((factors.len(), nobserved));
+    for (irow, factor) in factors.iter().enumerate() {
+        assert_eq!(factor.as_vec().len(), nobserved);
+        for (icol, value) in factor.as_vec().iter().enumerate() {
+            rows[(irow, icol)] = value.to_f32().unwrap();
+        }
+    }
+    rows
+}
+
+#[test]
+fn test_hungarian() {
+    let cost = vec![
+        vec![4., 1., 3.],
+        vec![2., 0., 5.],
+        vec![3., 2., 2.],
+    ];
+    // 1 + 2 + 2 = 5 is the minimum
+    assert_eq!(hungarian(&cost), vec![1, 0, 2]);
+}
+
+#[test]
+fn test_match_factors_permuted() {
+    let mut truth = Array2D::<FloatT>::zeros((2, 3));
+    truth[(0, 0)] = 1.;
+    truth[(1, 2)] = 2.;
+
+    // more learned factors than truth. scaled and permuted.
+    let mut learned = Array2D::<FloatT>::zeros((3, 3));
+    learned[(0, 1)] = 1.;
+    learned[(1, 2)] = 5.;
+    learned[(2, 0)] = 3.;
+
+    let recovery = match_factors(&learned, &truth);
+    assert_eq!(recovery.matches[0].ilearned, Some(2));
+    assert_eq!(recovery.matches[1].ilearned, Some(1));
+    assert_eq!(recovery.recovery_score, 1.);
+}
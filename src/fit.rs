@@ -0,0 +1,106 @@
+/*!
+long running fits with periodic checkpoints.
+
+a `Fit` owns the model, the iteration counter and the hyperparameters
+(alpha schedule and seed).
+it can be checkpointed at any iteration and resumed later.
+a resumed fit gives exactly the same result as an uninterrupted one:
+the iterations are deterministic and only the initialization
+draws random numbers. the random number generator is therefore
+fully described by the seed.
+
+only `NMFBlas` can be checkpointed.
+`Checkpoint` stores its `hidden` and `weights`.
+the other models have no `Fit` yet.
+*/
+
+use std;
+use std::path::Path;
+
+use rand::{StdRng, SeedableRng};
+
+use helpers::Array2D;
+use initializer::Initializer;
+use nmf_blas::{NMFBlas, FloatT};
+use serialization::{Hyperparameters, Checkpoint, ModelLoadError};
+
+pub struct Fit {
+    pub nmf: NMFBlas,
+    /// number of iterations done so far
+    pub iteration: usize,
+    pub hyperparameters: Hyperparameters,
+}
+
+impl Fit {
+    /// initializes a new fit with `initializer`
+    /// seeded with `hyperparameters.seed`
+    pub fn new<I: Initializer>(
+        initializer: &I,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        hyperparameters: Hyperparameters,
+    ) -> Fit {
+        let mut rng: StdRng = SeedableRng::from_seed(&hyperparameters.seed[..]);
+        let nmf = NMFBlas::new_with(initializer, samples, nhidden, &mut rng);
+        Fit {
+            nmf: nmf,
+            iteration: 0,
+            hyperparameters: hyperparameters,
+        }
+    }
+
+    /// does one iteration with the `alpha` the schedule
+    /// gives for the current iteration
+    pub fn step(&mut self, samples: &mut Array2D<FloatT>) {
+        let alpha = self.hyperparameters.alpha_schedule.alpha(self.iteration);
+        self.nmf.iterate(samples, alpha);
+        self.iteration += 1;
+    }
+
+    /// iterates until `niterations` iterations are done in total.
+    /// saves a checkpoint to `checkpoint_path` every `checkpoint_every` iterations
+    /// and when done.
+    pub fn run<P: AsRef<Path>>(
+        &mut self,
+        samples: &mut Array2D<FloatT>,
+        niterations: usize,
+        checkpoint_every: usize,
+        checkpoint_path: P,
+    ) -> std::io::Result<()> {
+        assert!(0 < checkpoint_every);
+        while self.iteration < niterations {
+            self.step(samples);
+            if 0 == self.iteration % checkpoint_every {
+                try!(self.save_checkpoint(checkpoint_path.as_ref()));
+            }
+        }
+        self.save_checkpoint(checkpoint_path.as_ref())
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            model: self.nmf.to_saved(self.hyperparameters.clone()),
+            iteration: self.iteration,
+        }
+    }
+
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        self.checkpoint().save(path)
+    }
+
+    /// continues where `checkpoint` stopped
+    pub fn from_checkpoint(checkpoint: &Checkpoint) -> Result<Fit, ModelLoadError> {
+        let nmf = try!(NMFBlas::from_saved(&checkpoint.model));
+        Ok(Fit {
+            nmf: nmf,
+            iteration: checkpoint.iteration,
+            hyperparameters: checkpoint.model.hyperparameters.clone(),
+        })
+    }
+
+    /// continues where the checkpoint saved at `path` stopped
+    pub fn resume<P: AsRef<Path>>(path: P) -> Result<Fit, ModelLoadError> {
+        let checkpoint = try!(Checkpoint::load(path));
+        Fit::from_checkpoint(&checkpoint)
+    }
+}
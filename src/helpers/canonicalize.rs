@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+use std::ops::{Index, IndexMut};
+
+use num::traits::Float;
+
+use helpers::Dims;
+
+/// orders `a` before `b` if `a` is larger.
+/// `NaN` is not ordered so it comes last instead of panicking.
+/// use it to sort values in descending order with `sort_by`.
+pub fn descending_nan_last<T: Float>(a: &T, b: &T) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => b.partial_cmp(a).unwrap(),
+    }
+}
+
+/// removes the scale and permutation ambiguity of a factorization
+/// without changing `weights * hidden`.
+/// rescales each row of `hidden` to unit (euclidean) norm
+/// and moves the scale into the corresponding column of `weights`.
+/// then sorts the hidden variables by their total contribution
+/// `sum(weights column) * sum(hidden row)` in descending order.
+/// hidden variables whose contribution is `NaN` come last.
+///
+/// works for any matrix type that is indexed by `(row, col)`.
+/// `hidden` has the shape `(nhidden, nobserved)`.
+/// `weights` has the shape `(nsamples, nhidden)`.
+pub fn canonicalize_factors<T, M>(
+    hidden: &mut M, weights: &mut M, nhidden: usize, nobserved: usize, nsamples: usize)
+    where T: Float,
+          M: Index<Dims, Output=T> + IndexMut<Dims> + Clone
+{
+    for ihidden in 0..nhidden {
+        let mut norm = T::zero();
+        for iobserved in 0..nobserved {
+            norm = norm + hidden[(ihidden, iobserved)] * hidden[(ihidden, iobserved)];
+        }
+        let norm = norm.sqrt();
+        if T::zero() == norm {
+            continue;
+        }
+        for iobserved in 0..nobserved {
+            hidden[(ihidden, iobserved)] = hidden[(ihidden, iobserved)] / norm;
+        }
+        for isample in 0..nsamples {
+            weights[(isample, ihidden)] = weights[(isample, ihidden)] * norm;
+        }
+    }
+
+    let mut contributions: Vec<(usize, T)> = (0..nhidden).map(|ihidden| {
+        let mut hidden_sum = T::zero();
+        for iobserved in 0..nobserved {
+            hidden_sum = hidden_sum + hidden[(ihidden, iobserved)];
+        }
+        let mut weights_sum = T::zero();
+        for isample in 0..nsamples {
+            weights_sum = weights_sum + weights[(isample, ihidden)];
+        }
+        (ihidden, hidden_sum * weights_sum)
+    }).collect();
+    // stable such that ties keep their order
+    contributions.sort_by(|a, b| descending_nan_last(&a.1, &b.1));
+
+    let old_hidden = hidden.clone();
+    let old_weights = weights.clone();
+    for (inew, &(iold, _)) in contributions.iter().enumerate() {
+        for iobserved in 0..nobserved {
+            hidden[(inew, iobserved)] = old_hidden[(iold, iobserved)];
+        }
+        for isample in 0..nsamples {
+            weights[(isample, inew)] = old_weights[(isample, iold)];
+        }
+    }
+}
+
+#[test]
+fn test_canonicalize_factors_nan_comes_last() {
+    use helpers::Array2D;
+    use std::f32;
+
+    let mut hidden = Array2D::<f32>::zeros((2, 2));
+    hidden[(0, 0)] = f32::NAN;
+    hidden[(1, 1)] = 1.;
+    let mut weights = Array2D::<f32>::from_elem((3, 2), 1.);
+
+    canonicalize_factors(&mut hidden, &mut weights, 2, 2, 3);
+
+    assert_eq!(hidden[(0, 1)], 1.);
+    assert!(hidden[(1, 0)].is_nan());
+}
+
+#[test]
+fn test_descending_nan_last() {
+    use std::f32;
+
+    let mut values = vec![1., f32::NAN, 3., 2.];
+    values.sort_by(descending_nan_last);
+    assert_eq!(&values[..3], &[3., 2., 1.]);
+    assert!(values[3].is_nan());
+}
@@ -0,0 +1,172 @@
+use std::cmp::PartialOrd;
+
+use num::traits::{ToPrimitive, Float};
+use nalgebra::DMat;
+use ndarray::{ArrayBase, Data};
+use image::{ImageBuffer, Rgb, DynamicImage};
+
+use helpers::Dims;
+use helpers::to_image::ToImage;
+
+/// `viridis` sampled at `0, 1/8, ..., 1`
+const VIRIDIS: [[f64; 3]; 9] = [
+    [68., 1., 84.],
+    [71., 44., 122.],
+    [59., 81., 139.],
+    [44., 113., 142.],
+    [33., 144., 141.],
+    [39., 173., 129.],
+    [92., 200., 99.],
+    [170., 220., 50.],
+    [253., 231., 37.],
+];
+
+/// blue to light gray to red
+const DIVERGING: [[f64; 3]; 3] = [
+    [59., 76., 192.],
+    [221., 221., 221.],
+    [180., 4., 38.],
+];
+
+/// maps values to colors.
+/// use it to visualize matrices that are not images such as `weights`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colormap {
+    /// perceptually uniform dark blue to yellow.
+    /// for nonnegative values.
+    Viridis,
+    /// blue for negative, gray for `0`, red for positive values.
+    /// for signed values such as differences.
+    Diverging,
+}
+
+/// linear interpolation between the equidistant `colors` at `position` in `0..1`
+fn interpolate(colors: &[[f64; 3]], position: f64) -> [u8; 3] {
+    let position = position.max(0.).min(1.) * (colors.len() - 1) as f64;
+    let lower = (position.floor() as usize).min(colors.len() - 2);
+    let fraction = position - lower as f64;
+    let mut color = [0; 3];
+    for channel in 0..3 {
+        let value = colors[lower][channel] * (1. - fraction) + colors[lower + 1][channel] * fraction;
+        color[channel] = value.round() as u8;
+    }
+    color
+}
+
+impl Colormap {
+    /// returns the color of `position` which is clamped to `0..1`.
+    /// for `Diverging` `0.5` is the center.
+    pub fn color(&self, position: f64) -> [u8; 3] {
+        match *self {
+            Colormap::Viridis => interpolate(&VIRIDIS[..], position),
+            Colormap::Diverging => interpolate(&DIVERGING[..], position),
+        }
+    }
+}
+
+/// a matrix rendered with a colormap.
+/// works for `DMat`, `Array2D` and views.
+/// `Viridis` maps the minimum to `0` and the maximum to `1`.
+/// `Diverging` maps `0` to the center and the largest absolute value
+/// to the ends.
+pub struct Colormapped<'a, M: 'a> {
+    pub matrix: &'a M,
+    pub colormap: Colormap,
+}
+
+pub fn colormapped<M>(matrix: &M, colormap: Colormap) -> Colormapped<M> {
+    Colormapped {
+        matrix: matrix,
+        colormap: colormap,
+    }
+}
+
+/// returns a color image of the `value(row, col)` mapped with `colormap`
+fn colormap_image<F>(nrows: usize, ncols: usize, colormap: Colormap, value: F) -> DynamicImage
+    where F: Fn(Dims) -> f64
+{
+    let mut min = ::std::f64::INFINITY;
+    let mut max = ::std::f64::NEG_INFINITY;
+    for row in 0..nrows {
+        for col in 0..ncols {
+            let value = value((row, col));
+            assert!(!value.is_nan());
+            min = min.min(value);
+            max = max.max(value);
+        }
+    }
+    let max_abs = min.abs().max(max.abs());
+
+    let position = |value: f64| {
+        match colormap {
+            Colormap::Viridis => {
+                if max > min { (value - min) / (max - min) } else { 0. }
+            }
+            Colormap::Diverging => {
+                if 0. < max_abs { 0.5 + 0.5 * value / max_abs } else { 0.5 }
+            }
+        }
+    };
+
+    let mut image_buffer = ImageBuffer::new(ncols as u32, nrows as u32);
+    for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
+        *pixel = Rgb(colormap.color(position(value((y as usize, x as usize)))));
+    }
+    DynamicImage::ImageRgb8(image_buffer)
+}
+
+impl<'a, T: ToPrimitive + PartialOrd + Float> ToImage for Colormapped<'a, DMat<T>> {
+    fn to_image(&self) -> DynamicImage {
+        colormap_image(self.matrix.nrows(), self.matrix.ncols(), self.colormap,
+                       |index| self.matrix[index].to_f64().unwrap())
+    }
+}
+
+impl<'a, S, T> ToImage for Colormapped<'a, ArrayBase<S, Dims>>
+    where S: Data<Elem=T>,
+          T: ToPrimitive + PartialOrd + Float
+{
+    fn to_image(&self) -> DynamicImage {
+        let shape = self.matrix.shape();
+        colormap_image(shape[0], shape[1], self.colormap,
+                       |index| self.matrix[index].to_f64().unwrap())
+    }
+}
+
+#[test]
+fn test_colormap_ends() {
+    assert_eq!(Colormap::Viridis.color(0.), [68, 1, 84]);
+    assert_eq!(Colormap::Viridis.color(1.), [253, 231, 37]);
+    assert_eq!(Colormap::Viridis.color(2.), [253, 231, 37]);
+    assert_eq!(Colormap::Diverging.color(0.5), [221, 221, 221]);
+}
+
+#[test]
+fn test_colormapped_diverging_center() {
+    let mut matrix = DMat::<f32>::new_zeros(1, 3);
+    matrix[(0, 0)] = -2.;
+    matrix[(0, 2)] = 1.;
+    match colormapped(&matrix, Colormap::Diverging).to_image() {
+        DynamicImage::ImageRgb8(buffer) => {
+            assert_eq!(buffer.get_pixel(0, 0).data, [59, 76, 192]);
+            assert_eq!(buffer.get_pixel(1, 0).data, [221, 221, 221]);
+        }
+        _ => panic!("expected an rgb image"),
+    }
+}
+
+#[test]
+fn test_colormapped_array_viridis() {
+    use helpers::Array2D;
+
+    let mut array = Array2D::<f32>::zeros((2, 1));
+    array[(1, 0)] = 3.;
+    match colormapped(&array, Colormap::Viridis).to_image() {
+        DynamicImage::ImageRgb8(buffer) => {
+            assert_eq!(buffer.dimensions(), (1, 2));
+            assert_eq!(buffer.get_pixel(0, 0).data, [68, 1, 84]);
+            assert_eq!(buffer.get_pixel(0, 1).data, [253, 231, 37]);
+        }
+        _ => panic!("expected an rgb image"),
+    }
+}
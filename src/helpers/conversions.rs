@@ -0,0 +1,42 @@
+use nalgebra::{DMat, Transpose};
+use ndarray::{ArrayBase, Data};
+
+use helpers::{Dims, Array2D};
+
+/// returns `array` (or a view of it) as a `DMat`.
+/// copies the values once.
+pub fn array_to_dmat<S, T>(array: &ArrayBase<S, Dims>) -> DMat<T>
+    where S: Data<Elem=T>,
+          T: Clone + Copy
+{
+    let (nrows, ncols) = (array.shape()[0], array.shape()[1]);
+    // `iter` visits the values row by row
+    let values: Vec<T> = array.iter().cloned().collect();
+    DMat::from_row_vec(nrows, ncols, &values[..])
+}
+
+/// returns `matrix` as an `Array2D`.
+/// copies the values once.
+pub fn dmat_to_array<T: Clone + Copy>(matrix: &DMat<T>) -> Array2D<T> {
+    let (nrows, ncols) = (matrix.nrows(), matrix.ncols());
+    // `DMat` stores the values col by col. `Array2D` row by row.
+    // the cols of the transpose are the rows of `matrix`.
+    let values = matrix.transpose().into_vec();
+    assert_eq!(values.len(), nrows * ncols);
+    // the length matches the shape
+    unsafe { Array2D::from_vec_dim((nrows, ncols), values) }
+}
+
+#[test]
+fn test_conversions_roundtrip() {
+    let mut array = Array2D::<f32>::zeros((2, 3));
+    for (index, x) in array.iter_mut().enumerate() {
+        *x = index as f32;
+    }
+    let matrix = array_to_dmat(&array);
+    assert_eq!(matrix.nrows(), 2);
+    assert_eq!(matrix.ncols(), 3);
+    assert_eq!(matrix[(1, 0)], 3.);
+    assert_eq!(matrix[(0, 2)], 2.);
+    assert_eq!(dmat_to_array(&matrix), array);
+}
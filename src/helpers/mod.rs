@@ -24,7 +24,19 @@ mod random;
 pub use self::random::random01;
 
 pub mod to_image;
-pub use self::to_image::ToImage;
+pub use self::to_image::{ToImage, RgbMatrices};
+
+pub mod colormap;
+pub use self::colormap::{Colormap, colormapped};
 
 mod magnify;
 pub use self::magnify::magnify;
+
+mod svd;
+pub use self::svd::{TruncatedSvd, truncated_svd};
+
+mod canonicalize;
+pub use self::canonicalize::{canonicalize_factors, descending_nan_last};
+
+mod conversions;
+pub use self::conversions::{array_to_dmat, dmat_to_array};
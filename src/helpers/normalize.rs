@@ -1,28 +1,104 @@
 use std::ops::Div;
 
 use nalgebra::DMat;
+use ndarray::{ArrayBase, DataMut};
 use num::{Zero};
 
+use helpers::{Dims, Array2D};
 use helpers::PartialMaxIteratorExt;
+use helpers::to_image::RgbMatrices;
 
 pub trait Normalize {
     /// divide all values in `self` by max value in `self`
     fn normalize(self) -> Self;
 }
 
+/// returns the max of `values` or `None` if it is zero
+/// and dividing would change nothing.
+///
+/// # Panics
+/// panics if `values` is empty
+fn nonzero_max<T, I>(values: I) -> Option<T>
+    where T: PartialOrd + Zero,
+          I: Iterator<Item=T>
+{
+    let max = values.partial_max().expect("can't normalize an empty matrix");
+    if max == T::zero() {
+        None
+    } else {
+        Some(max)
+    }
+}
+
+/// `value <- value / max` for all `values`
+fn divide_by<'a, T, I>(values: I, max: T)
+    where T: 'a + Copy + Div<Output=T>,
+          I: Iterator<Item=&'a mut T>
+{
+    for x in values {
+        *x = *x / max;
+    }
+}
+
 impl<T> Normalize for DMat<T>
-    where T: PartialOrd + Clone + Copy + Zero,
-          DMat<T>: Div<T, Output=DMat<T>>
+    where T: PartialOrd + Clone + Copy + Zero + Div<Output=T>
 {
-    fn normalize(self) -> Self {
-        assert!(0 < self.ncols());
-        assert!(0 < self.nrows());
-        let max = self.as_vec().iter().cloned().partial_max().unwrap();
-        if max == T::zero() {
-            self
-        } else {
-            self.div(max)
+    fn normalize(mut self) -> Self {
+        if let Some(max) = nonzero_max(self.as_vec().iter().cloned()) {
+            divide_by(self.as_mut_vec().iter_mut(), max);
         }
+        self
+    }
+}
+
+/// divides in place. works for `Array2D` and mutable views.
+impl<S, T> Normalize for ArrayBase<S, Dims>
+    where S: DataMut<Elem=T>,
+          T: PartialOrd + Clone + Copy + Zero + Div<Output=T>
+{
+    fn normalize(mut self) -> Self {
+        if let Some(max) = nonzero_max(self.iter().cloned()) {
+            divide_by(self.iter_mut(), max);
+        }
+        self
+    }
+}
+
+/// divides all channels by the max value of all channels
+/// such that the colors keep their hue
+impl<T> Normalize for RgbMatrices<DMat<T>>
+    where T: PartialOrd + Clone + Copy + Zero + Div<Output=T>
+{
+    fn normalize(mut self) -> Self {
+        let max = nonzero_max(self.red.as_vec().iter()
+            .chain(self.green.as_vec().iter())
+            .chain(self.blue.as_vec().iter())
+            .cloned());
+        if let Some(max) = max {
+            divide_by(self.red.as_mut_vec().iter_mut()
+                .chain(self.green.as_mut_vec().iter_mut())
+                .chain(self.blue.as_mut_vec().iter_mut()), max);
+        }
+        self
+    }
+}
+
+/// divides all channels by the max value of all channels
+/// such that the colors keep their hue
+impl<T> Normalize for RgbMatrices<Array2D<T>>
+    where T: PartialOrd + Clone + Copy + Zero + Div<Output=T>
+{
+    fn normalize(mut self) -> Self {
+        let max = nonzero_max(self.red.iter()
+            .chain(self.green.iter())
+            .chain(self.blue.iter())
+            .cloned());
+        if let Some(max) = max {
+            divide_by(self.red.iter_mut()
+                .chain(self.green.iter_mut())
+                .chain(self.blue.iter_mut()), max);
+        }
+        self
     }
 }
 
@@ -43,3 +119,21 @@ fn test_normalize_values() {
     let mat = DMat::<f64>::from_elem(10, 10, 0.5);
     assert_eq!(DMat::<f64>::new_ones(10, 10), mat.normalize());
 }
+
+#[test]
+fn test_normalize_array() {
+    let mut array = Array2D::<f64>::zeros((2, 2));
+    array[(0, 1)] = 4.;
+    array[(1, 0)] = 2.;
+    let array = array.normalize();
+    assert_eq!(array[(0, 1)], 1.);
+    assert_eq!(array[(1, 0)], 0.5);
+    assert_eq!(Array2D::<f64>::zeros((2, 2)).normalize(), Array2D::<f64>::zeros((2, 2)));
+}
+
+#[test]
+#[should_panic(expected = "can't normalize an empty matrix")]
+fn test_normalize_empty_rgb() {
+    let empty = Array2D::<f64>::zeros((0, 3));
+    RgbMatrices { red: empty.clone(), green: empty.clone(), blue: empty }.normalize();
+}
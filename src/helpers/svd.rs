@@ -0,0 +1,183 @@
+use num::traits::{Float, FromPrimitive};
+
+use helpers::Array2D;
+
+/// the `rank` largest singular values and vectors of a matrix
+/// such that `matrix ~= u * diag(s) * v`
+pub struct TruncatedSvd<T> {
+    /// left singular vectors. one per column. shape `(nrows, rank)`
+    pub u: Array2D<T>,
+    /// singular values in descending order
+    pub s: Vec<T>,
+    /// right singular vectors. one per row. shape `(rank, ncols)`
+    pub v: Array2D<T>,
+}
+
+/// `result <- a * a.transpose()` if `rows` else `a.transpose() * a`
+fn gram<T: Float>(a: &Array2D<T>, rows: bool) -> Array2D<T> {
+    let (nrows, ncols) = (a.shape()[0], a.shape()[1]);
+    let (size, ninner) = if rows { (nrows, ncols) } else { (ncols, nrows) };
+    let at = |outer: usize, inner: usize| {
+        if rows { a[(outer, inner)] } else { a[(inner, outer)] }
+    };
+    let mut result = Array2D::<T>::from_elem((size, size), T::zero());
+    for i in 0..size {
+        for j in i..size {
+            let mut sum = T::zero();
+            for k in 0..ninner {
+                sum = sum + at(i, k) * at(j, k);
+            }
+            result[(i, j)] = sum;
+            result[(j, i)] = sum;
+        }
+    }
+    result
+}
+
+/// orthonormalizes the columns of `basis` in place (modified gram-schmidt).
+/// columns that become zero are left zero.
+fn orthonormalize_columns<T: Float>(basis: &mut Array2D<T>) {
+    let (size, rank) = (basis.shape()[0], basis.shape()[1]);
+    for j in 0..rank {
+        for previous in 0..j {
+            let mut dot = T::zero();
+            for i in 0..size {
+                dot = dot + basis[(i, j)] * basis[(i, previous)];
+            }
+            for i in 0..size {
+                basis[(i, j)] = basis[(i, j)] - dot * basis[(i, previous)];
+            }
+        }
+        let mut norm = T::zero();
+        for i in 0..size {
+            norm = norm + basis[(i, j)] * basis[(i, j)];
+        }
+        let norm = norm.sqrt();
+        if T::zero() < norm {
+            for i in 0..size {
+                basis[(i, j)] = basis[(i, j)] / norm;
+            }
+        }
+    }
+}
+
+/// returns the `rank` largest singular values and vectors of `matrix`.
+/// uses `niterations` steps of subspace iteration on the smaller
+/// of the two gram matrices of `matrix`.
+/// deterministic: the same `matrix` always gives the same result.
+pub fn truncated_svd<T>(matrix: &Array2D<T>, rank: usize, niterations: usize) -> TruncatedSvd<T>
+    where T: Float + FromPrimitive
+{
+    let (nrows, ncols) = (matrix.shape()[0], matrix.shape()[1]);
+    assert!(0 < rank);
+    assert!(rank <= nrows && rank <= ncols);
+
+    // eigenvectors of the smaller gram matrix are the
+    // singular vectors of the smaller side
+    let rows_are_smaller = nrows < ncols;
+    let gram = gram(matrix, rows_are_smaller);
+    let size = gram.shape()[0];
+
+    // deterministic start basis that is unlikely to be
+    // orthogonal to any of the eigenvectors
+    let mut basis = Array2D::<T>::from_elem((size, rank), T::zero());
+    for i in 0..size {
+        for j in 0..rank {
+            let x = T::from_usize((i + 1) * (j + 7) % 101 + 1).unwrap();
+            basis[(i, j)] = x.sin().abs() + T::from_f64(0.1).unwrap();
+        }
+    }
+    orthonormalize_columns(&mut basis);
+
+    for _ in 0..niterations {
+        let mut next = Array2D::<T>::from_elem((size, rank), T::zero());
+        for i in 0..size {
+            for j in 0..rank {
+                let mut sum = T::zero();
+                for k in 0..size {
+                    sum = sum + gram[(i, k)] * basis[(k, j)];
+                }
+                next[(i, j)] = sum;
+            }
+        }
+        orthonormalize_columns(&mut next);
+        basis = next;
+    }
+
+    // eigenvalues of the gram matrix are the squared singular values
+    let mut eigenvalues: Vec<(usize, T)> = (0..rank).map(|j| {
+        let mut value = T::zero();
+        for i in 0..size {
+            for k in 0..size {
+                value = value + basis[(i, j)] * gram[(i, k)] * basis[(k, j)];
+            }
+        }
+        (j, value.max(T::zero()))
+    }).collect();
+    eigenvalues.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let mut u = Array2D::<T>::from_elem((nrows, rank), T::zero());
+    let mut v = Array2D::<T>::from_elem((rank, ncols), T::zero());
+    let mut s = Vec::with_capacity(rank);
+
+    for (isingular, &(j, eigenvalue)) in eigenvalues.iter().enumerate() {
+        let singular_value = eigenvalue.sqrt();
+        s.push(singular_value);
+        if rows_are_smaller {
+            for i in 0..nrows {
+                u[(i, isingular)] = basis[(i, j)];
+            }
+            // v = u.transpose() * matrix / singular_value
+            if T::zero() < singular_value {
+                for col in 0..ncols {
+                    let mut sum = T::zero();
+                    for row in 0..nrows {
+                        sum = sum + basis[(row, j)] * matrix[(row, col)];
+                    }
+                    v[(isingular, col)] = sum / singular_value;
+                }
+            }
+        } else {
+            for i in 0..ncols {
+                v[(isingular, i)] = basis[(i, j)];
+            }
+            // u = matrix * v.transpose() / singular_value
+            if T::zero() < singular_value {
+                for row in 0..nrows {
+                    let mut sum = T::zero();
+                    for col in 0..ncols {
+                        sum = sum + matrix[(row, col)] * basis[(col, j)];
+                    }
+                    u[(row, isingular)] = sum / singular_value;
+                }
+            }
+        }
+    }
+
+    TruncatedSvd {
+        u: u,
+        s: s,
+        v: v,
+    }
+}
+
+#[test]
+fn test_truncated_svd_rank_one() {
+    // outer product of [1, 2] and [3, 0, 4] has the single singular value 5 * sqrt(5)
+    let mut matrix = Array2D::<f64>::zeros((2, 3));
+    let left = [1., 2.];
+    let right = [3., 0., 4.];
+    for row in 0..2 {
+        for col in 0..3 {
+            matrix[(row, col)] = left[row] * right[col];
+        }
+    }
+    let svd = truncated_svd(&matrix, 1, 20);
+    assert!((svd.s[0] - 5. * 5f64.sqrt()).abs() < 1e-9);
+    for row in 0..2 {
+        for col in 0..3 {
+            let reconstructed = svd.u[(row, 0)] * svd.s[0] * svd.v[(0, col)];
+            assert!((reconstructed - matrix[(row, col)]).abs() < 1e-9);
+        }
+    }
+}
@@ -3,8 +3,11 @@ use std::cmp::PartialOrd;
 
 use num::traits::{Zero, One, FromPrimitive, ToPrimitive, Float};
 use nalgebra::{DMat};
+use ndarray::{ArrayBase, Data};
 use image;
-use image::{ImageBuffer, Luma, DynamicImage};
+use image::{ImageBuffer, Luma, Rgb, DynamicImage};
+
+use helpers::Dims;
 
 quick_error! {
     #[derive(Debug)]
@@ -25,28 +28,126 @@ pub trait ToImage {
     /// # Panics
     /// panics unless all the values in `self` are between
     /// `0` (inclusive) and `1` (inclusive)
-    fn save_to_png(&self, filename: &str) -> Result<(), ImageSaveError>;
+    fn save_to_png(&self, filename: &str) -> Result<(), ImageSaveError> {
+        let image = self.to_image();
+        let path = std::path::Path::new(filename);
+        let mut file = try!(std::fs::File::create(&path).map_err(ImageSaveError::Io));
+        image.save(&mut file, image::PNG).map_err(ImageSaveError::Image)
+    }
+}
+
+/// converts `value` from `0..1` to `0..255`
+///
+/// # Panics
+/// panics unless `value` is between `0` (inclusive) and `1` (inclusive)
+fn to_byte<T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float>(value: T) -> u8 {
+    assert!(!value.is_nan());
+    assert!(T::zero() <= value);
+    assert!(value <= T::one());
+    let max = T::from_u8(std::u8::MAX).unwrap();
+    (value * max).round().to_u8().unwrap()
+}
+
+/// returns a grayscale image with the brightness `value(row, col)`
+fn luma_image<T, F>(nrows: usize, ncols: usize, value: F) -> DynamicImage
+    where T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float,
+          F: Fn(Dims) -> T
+{
+    let mut image_buffer = ImageBuffer::new(ncols as u32, nrows as u32);
+    for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
+        *pixel = Luma([to_byte(value((y as usize, x as usize)))]);
+    }
+    DynamicImage::ImageLuma8(image_buffer)
+}
+
+/// returns a color image with the red, green and blue amounts `rgb(row, col)`
+fn rgb_image<T, F>(nrows: usize, ncols: usize, rgb: F) -> DynamicImage
+    where T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float,
+          F: Fn(Dims) -> [T; 3]
+{
+    let mut image_buffer = ImageBuffer::new(ncols as u32, nrows as u32);
+    for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
+        let amounts = rgb((y as usize, x as usize));
+        *pixel = Rgb([to_byte(amounts[0]), to_byte(amounts[1]), to_byte(amounts[2])]);
+    }
+    DynamicImage::ImageRgb8(image_buffer)
 }
 
 impl<T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float> ToImage for DMat<T> {
     fn to_image(&self) -> DynamicImage {
-        let mut image_buffer = ImageBuffer::new(self.ncols() as u32, self.nrows() as u32);
-        for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
-            let value = self[(y as usize, x as usize)];
-            assert!(!value.is_nan());
-            assert!(T::zero() <= value);
-            assert!(value <= T::one());
-            let max = T::from_u8(std::u8::MAX).unwrap();
-            let byte = (value * max).round().to_u8().unwrap();
-            *pixel = Luma([byte]);
+        luma_image(self.nrows(), self.ncols(), |index| self[index])
+    }
+}
+
+/// works for `Array2D` and views.
+/// one pixel row per row.
+impl<S, T> ToImage for ArrayBase<S, Dims>
+    where S: Data<Elem=T>,
+          T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float
+{
+    fn to_image(&self) -> DynamicImage {
+        luma_image(self.shape()[0], self.shape()[1], |index| self[index])
+    }
+}
+
+/// the red, green and blue channels of a color image.
+/// all have the same shape.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbMatrices<M> {
+    pub red: M,
+    pub green: M,
+    pub blue: M,
+}
+
+impl<T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float> ToImage for RgbMatrices<DMat<T>> {
+    fn to_image(&self) -> DynamicImage {
+        let (nrows, ncols) = (self.red.nrows(), self.red.ncols());
+        assert_eq!((nrows, ncols), (self.green.nrows(), self.green.ncols()));
+        assert_eq!((nrows, ncols), (self.blue.nrows(), self.blue.ncols()));
+        rgb_image(nrows, ncols, |index| [self.red[index], self.green[index], self.blue[index]])
+    }
+}
+
+impl<S, T> ToImage for RgbMatrices<ArrayBase<S, Dims>>
+    where S: Data<Elem=T>,
+          T: Zero + One + FromPrimitive + ToPrimitive + PartialOrd + Float
+{
+    fn to_image(&self) -> DynamicImage {
+        let shape = self.red.shape();
+        assert_eq!(shape, self.green.shape());
+        assert_eq!(shape, self.blue.shape());
+        rgb_image(shape[0], shape[1], |index| [self.red[index], self.green[index], self.blue[index]])
+    }
+}
+
+#[test]
+fn test_rgb_matrices_to_image() {
+    let rgb = RgbMatrices {
+        red: DMat::<f32>::from_elem(2, 3, 1.),
+        green: DMat::<f32>::from_elem(2, 3, 0.),
+        blue: DMat::<f32>::from_elem(2, 3, 0.5),
+    };
+    match rgb.to_image() {
+        DynamicImage::ImageRgb8(buffer) => {
+            assert_eq!(buffer.dimensions(), (3, 2));
+            assert_eq!(buffer.get_pixel(2, 1).data, [255, 0, 128]);
         }
-        DynamicImage::ImageLuma8(image_buffer)
+        _ => panic!("expected an rgb image"),
     }
+}
 
-    fn save_to_png(&self, filename: &str) -> Result<(), ImageSaveError> {
-        let image = self.to_image();
-        let path = std::path::Path::new(filename);
-        let mut file = try!(std::fs::File::create(&path).map_err(ImageSaveError::Io));
-        image.save(&mut file, image::PNG).map_err(ImageSaveError::Image)
+#[test]
+fn test_array_to_image() {
+    use helpers::Array2D;
+
+    let mut array = Array2D::<f32>::zeros((2, 3));
+    array[(1, 2)] = 1.;
+    match array.to_image() {
+        DynamicImage::ImageLuma8(buffer) => {
+            assert_eq!(buffer.dimensions(), (3, 2));
+            assert_eq!(buffer.get_pixel(2, 1).data, [255]);
+            assert_eq!(buffer.get_pixel(0, 0).data, [0]);
+        }
+        _ => panic!("expected a grayscale image"),
     }
 }
@@ -0,0 +1,438 @@
+/*!
+parts-based learning on a directory of same-size images.
+
+each image becomes one sample: its grayscale amounts flattened row by row.
+color images are loaded with `ImageSamples::load_rgb` which stacks
+the red, green and blue channels such that each sample holds
+all red pixels, then all green pixels, then all blue pixels.
+a factorization of such samples (faces for example)
+learns parts in the rows of `hidden`.
+`save_hidden_images` saves each of them as an image.
+see Lee and Seung, "Learning the parts of objects by
+non-negative matrix factorization" (1999).
+*/
+
+use std;
+use std::io::Read;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use image;
+use image::RgbImage;
+use nalgebra::DMat;
+
+use helpers::{Array2D, ToImage, RgbMatrices, Normalize, magnify};
+use helpers::to_image::ImageSaveError;
+use nmf_blas::{FloatT, ShapeAsTuple};
+use patches::luma_to_array;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ImageDirectoryError {
+        /// an error has occured when reading the directory or a file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// an image could not be decoded
+        Image(path: PathBuf, err: image::ImageError) {
+            display("can't decode {:?}: {:?}", path, err)
+        }
+        /// a PGM image could not be decoded
+        Pgm(path: PathBuf, message: String) {
+            display("can't decode {:?}: {}", path, message)
+        }
+        /// an image doesn't have the size of the first image
+        Size(path: PathBuf, found: (usize, usize), expected: (usize, usize)) {
+            display("{:?} has {:?} rows and cols but the first image has {:?}", path, found, expected)
+        }
+        /// the directory contains no images
+        Empty {}
+    }
+}
+
+/// the extensions of the files `image_paths` considers images
+pub const IMAGE_EXTENSIONS: &'static [&'static str] = &["pgm", "png", "ppm", "jpg", "jpeg", "gif"];
+
+/// returns the paths of the images in `directory` sorted by name
+pub fn image_paths<P: AsRef<Path>>(directory: P) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in try!(fs::read_dir(directory)) {
+        let path = try!(entry).path();
+        let is_image = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| IMAGE_EXTENSIONS.contains(&&extension.to_lowercase()[..]))
+            .unwrap_or(false);
+        if is_image {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// parses a binary (`P5`) or plain (`P2`) PGM image
+/// into brightness values in `0..1`. one row per pixel row.
+pub fn parse_pgm(bytes: &[u8]) -> Result<Array2D<FloatT>, String> {
+    // reads the next whitespace separated header token skipping `#` comments
+    fn token(bytes: &[u8], offset: &mut usize) -> Result<usize, String> {
+        loop {
+            while *offset < bytes.len() && (bytes[*offset] as char).is_whitespace() {
+                *offset += 1;
+            }
+            if *offset < bytes.len() && b'#' == bytes[*offset] {
+                while *offset < bytes.len() && b'\n' != bytes[*offset] {
+                    *offset += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let start = *offset;
+        while *offset < bytes.len() && !(bytes[*offset] as char).is_whitespace() {
+            *offset += 1;
+        }
+        std::str::from_utf8(&bytes[start..*offset])
+            .ok()
+            .and_then(|token| token.parse::<usize>().ok())
+            .ok_or_else(|| "expected a number in the header".to_string())
+    }
+
+    if bytes.len() < 2 || (&bytes[0..2] != b"P5" && &bytes[0..2] != b"P2") {
+        return Err("not a PGM image".to_string());
+    }
+    let binary = &bytes[0..2] == b"P5";
+    let mut offset = 2;
+    let width = try!(token(bytes, &mut offset));
+    let height = try!(token(bytes, &mut offset));
+    let max = try!(token(bytes, &mut offset));
+    if 0 == max || 65535 < max {
+        return Err(format!("invalid maximum value {}", max));
+    }
+
+    let npixels = try!(width.checked_mul(height)
+        .ok_or_else(|| format!("{} x {} pixels are too many", width, height)));
+    // check the size against the data before allocating
+    // such that a corrupt header can't make us allocate arbitrary amounts of memory
+    let nbytes = if max < 256 { 1 } else { 2 };
+    if binary {
+        // exactly one whitespace separates the header from the pixels
+        offset += 1;
+        if npixels.checked_mul(nbytes).map(|needed| bytes.len() < offset + needed).unwrap_or(true) {
+            return Err("too few pixels".to_string());
+        }
+    } else {
+        // every pixel takes at least one digit and one whitespace
+        if bytes.len() < offset || (bytes.len() - offset) / 2 + 1 < npixels {
+            return Err("too few pixels".to_string());
+        }
+    }
+
+    let mut array = Array2D::<FloatT>::zeros((height, width));
+    for (i, x) in array.iter_mut().enumerate() {
+        let value = if !binary {
+            try!(token(bytes, &mut offset))
+        } else if 1 == nbytes {
+            bytes[offset + i] as usize
+        } else {
+            // most significant byte first
+            let start = offset + 2 * i;
+            ((bytes[start] as usize) << 8) | bytes[start + 1] as usize
+        };
+        if max < value {
+            return Err(format!("pixel value {} is larger than the maximum value {}", value, max));
+        }
+        *x = value as FloatT / max as FloatT;
+    }
+    Ok(array)
+}
+
+/// whether `path` has the `pgm` extension (in any case).
+/// PGM files go through `parse_pgm` instead of `image`.
+fn is_pgm(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| "pgm" == extension.to_lowercase())
+        .unwrap_or(false)
+}
+
+/// returns the brightness values of the image at `path` in `0..1`
+/// (converted with `to_luma`). one row per pixel row.
+pub fn load_luma<P: AsRef<Path>>(path: P) -> Result<Array2D<FloatT>, ImageDirectoryError> {
+    let path = path.as_ref();
+    if is_pgm(path) {
+        let mut bytes = Vec::new();
+        let mut file = try!(File::open(path));
+        try!(file.read_to_end(&mut bytes));
+        parse_pgm(&bytes[..]).map_err(|message| ImageDirectoryError::Pgm(path.to_path_buf(), message))
+    } else {
+        let image = try!(image::open(path)
+            .map_err(|err| ImageDirectoryError::Image(path.to_path_buf(), err)));
+        Ok(luma_to_array(&image.to_luma()))
+    }
+}
+
+/// returns the red, green and blue amounts of `image` in `0..1`.
+/// one row per pixel row.
+pub fn rgb_to_channels(image: &RgbImage) -> RgbMatrices<Array2D<FloatT>> {
+    let (width, height) = image.dimensions();
+    let shape = (height as usize, width as usize);
+    let mut channels = RgbMatrices {
+        red: Array2D::<FloatT>::zeros(shape),
+        green: Array2D::<FloatT>::zeros(shape),
+        blue: Array2D::<FloatT>::zeros(shape),
+    };
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let index = (y as usize, x as usize);
+        channels.red[index] = pixel[0] as FloatT / 255.;
+        channels.green[index] = pixel[1] as FloatT / 255.;
+        channels.blue[index] = pixel[2] as FloatT / 255.;
+    }
+    channels
+}
+
+/// returns the red, green and blue channels stacked on top of each other.
+/// the result has three times as many rows as each channel.
+pub fn stack_channels(channels: &RgbMatrices<Array2D<FloatT>>) -> Array2D<FloatT> {
+    let (nrows, ncols) = channels.red.shape_as_tuple();
+    assert_eq!(channels.green.shape_as_tuple(), (nrows, ncols));
+    assert_eq!(channels.blue.shape_as_tuple(), (nrows, ncols));
+    let mut stacked = Array2D::<FloatT>::zeros((3 * nrows, ncols));
+    for (ichannel, channel) in [&channels.red, &channels.green, &channels.blue].iter().enumerate() {
+        for ((row, col), x) in channel.indexed_iter() {
+            stacked[(ichannel * nrows + row, col)] = *x;
+        }
+    }
+    stacked
+}
+
+/// returns the stacked red, green and blue amounts (see `stack_channels`)
+/// of the image at `path` in `0..1`.
+/// grayscale PGM images get three equal channels.
+pub fn load_stacked_rgb<P: AsRef<Path>>(path: P) -> Result<Array2D<FloatT>, ImageDirectoryError> {
+    let path = path.as_ref();
+    if is_pgm(path) {
+        let luma = try!(load_luma(path));
+        return Ok(stack_channels(&RgbMatrices {
+            red: luma.clone(),
+            green: luma.clone(),
+            blue: luma,
+        }));
+    }
+    let image = try!(image::open(path)
+        .map_err(|err| ImageDirectoryError::Image(path.to_path_buf(), err)));
+    Ok(stack_channels(&rgb_to_channels(&image.to_rgb())))
+}
+
+/// same-size images flattened into samples
+pub struct ImageSamples {
+    /// one image per row. the pixels row by row.
+    pub samples: Array2D<FloatT>,
+    /// `(nrows, ncols)` of every image
+    pub image_shape: (usize, usize),
+    /// `1` for grayscale and `3` for stacked red, green and blue
+    pub nchannels: usize,
+    /// one per row of `samples`
+    pub paths: Vec<PathBuf>,
+}
+
+impl ImageSamples {
+    /// loads the grayscale amounts of the images at `paths`.
+    /// all must have the same size.
+    pub fn load(paths: Vec<PathBuf>) -> Result<ImageSamples, ImageDirectoryError> {
+        ImageSamples::load_with(paths, 1, |path| load_luma(path))
+    }
+
+    /// loads the stacked red, green and blue amounts of the images at `paths`.
+    /// all must have the same size.
+    pub fn load_rgb(paths: Vec<PathBuf>) -> Result<ImageSamples, ImageDirectoryError> {
+        ImageSamples::load_with(paths, 3, |path| load_stacked_rgb(path))
+    }
+
+    fn load_with<F>(
+        paths: Vec<PathBuf>,
+        nchannels: usize,
+        load: F,
+    ) -> Result<ImageSamples, ImageDirectoryError>
+        where F: Fn(&Path) -> Result<Array2D<FloatT>, ImageDirectoryError>
+    {
+        if paths.is_empty() {
+            return Err(ImageDirectoryError::Empty);
+        }
+        let mut images: Vec<Array2D<FloatT>> = Vec::with_capacity(paths.len());
+        for path in paths.iter() {
+            let image = try!(load(path));
+            if let Some(first) = images.first() {
+                let expected = first.shape_as_tuple();
+                if image.shape_as_tuple() != expected {
+                    return Err(ImageDirectoryError::Size(path.clone(), image.shape_as_tuple(), expected));
+                }
+            }
+            images.push(image);
+        }
+
+        let stacked_shape = images[0].shape_as_tuple();
+        let image_shape = (stacked_shape.0 / nchannels, stacked_shape.1);
+        let npixels = stacked_shape.0 * stacked_shape.1;
+        let mut samples = Array2D::<FloatT>::zeros((images.len(), npixels));
+        for (isample, image) in images.iter().enumerate() {
+            for (ipixel, x) in image.iter().enumerate() {
+                samples[(isample, ipixel)] = *x;
+            }
+        }
+
+        Ok(ImageSamples {
+            samples: samples,
+            image_shape: image_shape,
+            nchannels: nchannels,
+            paths: paths,
+        })
+    }
+
+    /// loads all images in `directory` sorted by name
+    pub fn load_directory<P: AsRef<Path>>(directory: P) -> Result<ImageSamples, ImageDirectoryError> {
+        let paths = try!(image_paths(directory));
+        ImageSamples::load(paths)
+    }
+
+    /// like `load_directory` but loads the stacked red, green and blue amounts
+    pub fn load_directory_rgb<P: AsRef<Path>>(directory: P) -> Result<ImageSamples, ImageDirectoryError> {
+        let paths = try!(image_paths(directory));
+        ImageSamples::load_rgb(paths)
+    }
+}
+
+/// returns row `irow` of `matrix` as an image of `image_shape`
+/// normalized by its maximum
+pub fn row_to_image(matrix: &Array2D<FloatT>, irow: usize, image_shape: (usize, usize)) -> DMat<FloatT> {
+    let (nrows, ncols) = image_shape;
+    assert_eq!(matrix.shape()[1], nrows * ncols);
+    let mut image = DMat::new_zeros(nrows, ncols);
+    for row in 0..nrows {
+        for col in 0..ncols {
+            image[(row, col)] = matrix[(irow, row * ncols + col)];
+        }
+    }
+    image.normalize()
+}
+
+/// returns row `irow` of `matrix` which holds stacked red, green and blue
+/// amounts (see `stack_channels`) as an image of `image_shape`
+/// normalized by the maximum of all channels
+pub fn row_to_rgb_image(matrix: &Array2D<FloatT>, irow: usize, image_shape: (usize, usize)) -> RgbMatrices<DMat<FloatT>> {
+    let (nrows, ncols) = image_shape;
+    let npixels = nrows * ncols;
+    assert_eq!(matrix.shape()[1], 3 * npixels);
+    let channel = |ichannel: usize| {
+        let mut image = DMat::new_zeros(nrows, ncols);
+        for row in 0..nrows {
+            for col in 0..ncols {
+                image[(row, col)] = matrix[(irow, ichannel * npixels + row * ncols + col)];
+            }
+        }
+        image
+    };
+    RgbMatrices {
+        red: channel(0),
+        green: channel(1),
+        blue: channel(2),
+    }.normalize()
+}
+
+/// saves each row of `hidden` as the image `{prefix}{ihidden}.png`
+/// magnified by `magnification`
+pub fn save_hidden_images(
+    hidden: &Array2D<FloatT>,
+    image_shape: (usize, usize),
+    magnification: usize,
+    prefix: &str,
+) -> Result<(), ImageSaveError> {
+    for ihidden in 0..hidden.shape()[0] {
+        let image = magnify(row_to_image(hidden, ihidden, image_shape), magnification);
+        try!(image.save_to_png(&format!("{}{}.png", prefix, ihidden)[..]));
+    }
+    Ok(())
+}
+
+/// like `save_hidden_images` for `hidden` learned from samples
+/// loaded with `ImageSamples::load_rgb`
+pub fn save_hidden_rgb_images(
+    hidden: &Array2D<FloatT>,
+    image_shape: (usize, usize),
+    magnification: usize,
+    prefix: &str,
+) -> Result<(), ImageSaveError> {
+    for ihidden in 0..hidden.shape()[0] {
+        let image = row_to_rgb_image(hidden, ihidden, image_shape);
+        let image = RgbMatrices {
+            red: magnify(image.red, magnification),
+            green: magnify(image.green, magnification),
+            blue: magnify(image.blue, magnification),
+        };
+        try!(image.save_to_png(&format!("{}{}.png", prefix, ihidden)[..]));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_pgm() {
+    let plain = b"P2\n# a comment\n3 2\n4\n0 1 2\n3 4 4\n";
+    let array = parse_pgm(&plain[..]).unwrap();
+    assert_eq!(array.shape_as_tuple(), (2, 3));
+    assert_eq!(array[(0, 2)], 0.5);
+    assert_eq!(array[(1, 1)], 1.);
+
+    let mut binary = b"P5 2 1 255\n".to_vec();
+    binary.extend_from_slice(&[0, 255]);
+    let array = parse_pgm(&binary[..]).unwrap();
+    assert_eq!(array[(0, 0)], 0.);
+    assert_eq!(array[(0, 1)], 1.);
+}
+
+#[test]
+fn test_parse_pgm_errors() {
+    // the header claims far more pixels than there are bytes
+    assert!(parse_pgm(&b"P5 100000 100000 255\n\x00\x01"[..]).is_err());
+    assert!(parse_pgm(&b"P2 100000 100000 255\n0 1\n"[..]).is_err());
+    // pixel values above the maximum value
+    assert!(parse_pgm(&b"P2 2 1 4\n0 5\n"[..]).is_err());
+    assert!(parse_pgm(&b"P5 2 1 4\n\x00\x05"[..]).is_err());
+}
+
+#[test]
+fn test_is_pgm() {
+    assert!(is_pgm(Path::new("faces/1.pgm")));
+    assert!(is_pgm(Path::new("faces/1.PGM")));
+    assert!(!is_pgm(Path::new("faces/1.png")));
+    assert!(!is_pgm(Path::new("faces/pgm")));
+}
+
+#[test]
+fn test_row_to_image() {
+    let mut hidden = Array2D::<FloatT>::zeros((1, 6));
+    hidden[(0, 4)] = 2.;
+    hidden[(0, 5)] = 1.;
+    let image = row_to_image(&hidden, 0, (2, 3));
+    assert_eq!(image[(1, 1)], 1.);
+    assert_eq!(image[(1, 2)], 0.5);
+}
+
+#[test]
+fn test_stack_channels_and_row_to_rgb_image() {
+    let channels = RgbMatrices {
+        red: Array2D::<FloatT>::from_elem((1, 2), 0.5),
+        green: Array2D::<FloatT>::from_elem((1, 2), 0.25),
+        blue: Array2D::<FloatT>::from_elem((1, 2), 0.),
+    };
+    let stacked = stack_channels(&channels);
+    assert_eq!(stacked.shape_as_tuple(), (3, 2));
+    assert_eq!(stacked[(1, 1)], 0.25);
+
+    let mut samples = Array2D::<FloatT>::zeros((1, 6));
+    for (i, x) in stacked.iter().enumerate() {
+        samples[(0, i)] = *x;
+    }
+    let image = row_to_rgb_image(&samples, 0, (1, 2));
+    assert_eq!(image.red[(0, 1)], 1.);
+    assert_eq!(image.green[(0, 1)], 0.5);
+    assert_eq!(image.blue[(0, 1)], 0.);
+}
@@ -0,0 +1,365 @@
+/*!
+strategies to choose the initial `hidden` and `weights`.
+
+multiplicative updates only find a local minimum.
+which one they find depends on where they start.
+*/
+
+use rand::Rng;
+use num::{Float, Zero, NumCast};
+use nalgebra::DMat;
+
+use helpers::{Array2D, random01};
+use nmf_blas::{FloatT, ShapeAsTuple};
+use nndsvd::{nndsvd, NNDSVDVariant};
+
+/// chooses the initial `hidden` and `weights` for a factorization of `samples`
+pub trait Initializer {
+    /// returns `(hidden, weights)` for `nhidden` hidden variables.
+    /// `hidden` has shape `(nhidden, nobserved)`.
+    /// `weights` has shape `(nsamples, nhidden)`.
+    /// `samples` contains one observed per column, one sample per row.
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>);
+}
+
+/// returns the mean of all values in `samples`
+fn mean(samples: &Array2D<FloatT>) -> FloatT {
+    let (nsamples, nobserved) = samples.shape_as_tuple();
+    let sum = samples.iter().fold(0., |sum, x| sum + *x as f64);
+    (sum / (nsamples * nobserved) as f64) as FloatT
+}
+
+/// returns the `max` of uniform random values between `0` and `max`
+/// such that the mean of `weights * hidden` equals the mean of `samples`.
+///
+/// # Panics
+/// panics if `samples` has negative, `NaN` or infinite values
+/// (which would make all factors `NaN`)
+fn scaled_max(samples: &Array2D<FloatT>, nhidden: usize) -> FloatT {
+    for value in samples.iter() {
+        assert!(value.is_finite() && FloatT::zero() <= *value,
+                "samples must be finite and nonnegative");
+    }
+    // each entry of weights * hidden is the sum of `nhidden` products.
+    // values uniform between `0` and `max` have the mean `max / 2`.
+    2. * (mean(samples) / nhidden as FloatT).sqrt()
+}
+
+/// returns a matrix of `shape` with random values between `0` and `max`
+fn random_matrix<R: Rng>(shape: (usize, usize), max: FloatT, rng: &mut R) -> Array2D<FloatT> {
+    let mut result = Array2D::<FloatT>::zeros(shape);
+    for x in result.iter_mut() {
+        let random: FloatT = random01(rng);
+        *x = random * max;
+    }
+    result
+}
+
+/// uniform random values between `0` and `1`.
+/// the same as `NMFBlas::new_random01`.
+/// badly scaled for samples that are not between `0` and `1`.
+#[derive(Clone, Copy, Debug)]
+pub struct Random01;
+
+impl Random01 {
+    /// like `initialize` but only needs the shape of the samples
+    pub fn initialize_shape<R: Rng>(
+        &self,
+        nhidden: usize,
+        nobserved: usize,
+        nsamples: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        let hidden = random_matrix((nhidden, nobserved), 1., rng);
+        let weights = random_matrix((nsamples, nhidden), 1., rng);
+        (hidden, weights)
+    }
+}
+
+impl Initializer for Random01 {
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        let (nsamples, nobserved) = samples.shape_as_tuple();
+        self.initialize_shape(nhidden, nobserved, nsamples, rng)
+    }
+}
+
+/// uniform random values scaled such that the mean of `weights * hidden`
+/// equals the mean of `samples`
+#[derive(Clone, Copy, Debug)]
+pub struct ScaledRandom;
+
+impl Initializer for ScaledRandom {
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        let (nsamples, nobserved) = samples.shape_as_tuple();
+        let max = scaled_max(samples, nhidden);
+        let hidden = random_matrix((nhidden, nobserved), max, rng);
+        let weights = random_matrix((nsamples, nhidden), max, rng);
+        (hidden, weights)
+    }
+}
+
+/// random Acol.
+/// each row of `hidden` is the average of `nsamples_averaged`
+/// randomly chosen samples.
+/// `weights` are initialized like `ScaledRandom`.
+#[derive(Clone, Copy, Debug)]
+pub struct RandomAcol {
+    pub nsamples_averaged: usize,
+}
+
+impl Initializer for RandomAcol {
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        let (nsamples, nobserved) = samples.shape_as_tuple();
+        assert!(0 < self.nsamples_averaged, "random Acol needs nsamples_averaged > 0");
+        assert!(0 < nsamples, "random Acol needs at least one sample to average");
+        let max = scaled_max(samples, nhidden);
+
+        let mut hidden = Array2D::<FloatT>::zeros((nhidden, nobserved));
+        for ihidden in 0..nhidden {
+            for _ in 0..self.nsamples_averaged {
+                let isample = rng.gen_range(0, nsamples);
+                for iobserved in 0..nobserved {
+                    hidden[(ihidden, iobserved)] += samples[(isample, iobserved)];
+                }
+            }
+            for iobserved in 0..nobserved {
+                hidden[(ihidden, iobserved)] /= self.nsamples_averaged as FloatT;
+            }
+        }
+
+        let weights = random_matrix((nsamples, nhidden), max, rng);
+        (hidden, weights)
+    }
+}
+
+/// k-means seeding.
+/// the rows of `hidden` are the centroids found by
+/// `niterations` iterations of k-means on the samples.
+/// `weights` are the cluster memberships plus `0.2`
+/// such that multiplicative updates can still change them.
+#[derive(Clone, Copy, Debug)]
+pub struct KMeans {
+    pub niterations: usize,
+}
+
+fn squared_distance(
+    samples: &Array2D<FloatT>, isample: usize,
+    centroids: &Array2D<FloatT>, icentroid: usize,
+) -> FloatT {
+    let mut sum = FloatT::zero();
+    for iobserved in 0..samples.shape()[1] {
+        let difference = samples[(isample, iobserved)] - centroids[(icentroid, iobserved)];
+        sum += difference * difference;
+    }
+    sum
+}
+
+impl Initializer for KMeans {
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        let (nsamples, nobserved) = samples.shape_as_tuple();
+        assert!(nhidden <= nsamples, "k-means needs nhidden <= nsamples");
+
+        // start with distinct random samples as centroids
+        let mut indexes: Vec<usize> = (0..nsamples).collect();
+        rng.shuffle(&mut indexes[..]);
+        let mut centroids = Array2D::<FloatT>::zeros((nhidden, nobserved));
+        for ihidden in 0..nhidden {
+            for iobserved in 0..nobserved {
+                centroids[(ihidden, iobserved)] = samples[(indexes[ihidden], iobserved)];
+            }
+        }
+
+        let mut assignments = vec![0; nsamples];
+        for _ in 0..self.niterations {
+            // assign each sample to the closest centroid
+            for isample in 0..nsamples {
+                let mut closest = 0;
+                let mut closest_distance = FloatT::infinity();
+                for ihidden in 0..nhidden {
+                    let distance = squared_distance(samples, isample, &centroids, ihidden);
+                    if distance < closest_distance {
+                        closest = ihidden;
+                        closest_distance = distance;
+                    }
+                }
+                assignments[isample] = closest;
+            }
+
+            // move each centroid to the mean of its samples.
+            // centroids without samples stay where they are.
+            let mut sums = Array2D::<FloatT>::zeros((nhidden, nobserved));
+            let mut counts = vec![0usize; nhidden];
+            for isample in 0..nsamples {
+                let ihidden = assignments[isample];
+                counts[ihidden] += 1;
+                for iobserved in 0..nobserved {
+                    sums[(ihidden, iobserved)] += samples[(isample, iobserved)];
+                }
+            }
+            for ihidden in 0..nhidden {
+                if 0 < counts[ihidden] {
+                    for iobserved in 0..nobserved {
+                        centroids[(ihidden, iobserved)] =
+                            sums[(ihidden, iobserved)] / counts[ihidden] as FloatT;
+                    }
+                }
+            }
+        }
+
+        // multiplicative updates can't change zeros
+        for x in centroids.iter_mut() {
+            if FloatT::zero() == *x {
+                *x = FloatT::min_positive_value();
+            }
+        }
+
+        let mut weights = Array2D::<FloatT>::from_elem((nsamples, nhidden), 0.2);
+        for isample in 0..nsamples {
+            weights[(isample, assignments[isample])] += 1.;
+        }
+
+        (centroids, weights)
+    }
+}
+
+impl Initializer for NNDSVDVariant {
+    fn initialize<R: Rng>(
+        &self,
+        samples: &Array2D<FloatT>,
+        nhidden: usize,
+        rng: &mut R,
+    ) -> (Array2D<FloatT>, Array2D<FloatT>) {
+        nndsvd(samples, nhidden, *self, rng)
+    }
+}
+
+/// lets `initializer` choose the initial `hidden` and `weights`
+/// for a factorization of `samples` stored in a `DMat` of any float type.
+/// `samples` contains one observed per column, one sample per row.
+/// the result has the type of `samples`.
+pub fn initialize_dmat<I, R, T>(
+    initializer: &I,
+    samples: &DMat<T>,
+    nhidden: usize,
+    rng: &mut R,
+) -> (DMat<T>, DMat<T>)
+    where I: Initializer,
+          R: Rng,
+          T: Float
+{
+    // initializers work on `FloatT` arrays
+    let mut array = Array2D::<FloatT>::zeros((samples.nrows(), samples.ncols()));
+    for col in 0..samples.ncols() {
+        for row in 0..samples.nrows() {
+            array[(row, col)] = <FloatT as NumCast>::from(samples[(row, col)]).unwrap();
+        }
+    }
+
+    let (hidden, weights) = initializer.initialize(&array, nhidden, rng);
+
+    let to_dmat = |array: Array2D<FloatT>| {
+        let (nrows, ncols) = array.shape_as_tuple();
+        let mut result = DMat::from_elem(nrows, ncols, T::zero());
+        for ((row, col), x) in array.indexed_iter() {
+            result[(row, col)] = <T as NumCast>::from(*x).unwrap();
+        }
+        result
+    };
+
+    (to_dmat(hidden), to_dmat(weights))
+}
+
+#[test]
+fn test_scaled_random_matches_mean() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let samples = Array2D::<FloatT>::from_elem((200, 100), 50.);
+    let (hidden, weights) = ScaledRandom.initialize(&samples, 10, &mut rng);
+
+    let mut sum = 0.;
+    for isample in 0..200 {
+        for iobserved in 0..100 {
+            for ihidden in 0..10 {
+                sum += weights[(isample, ihidden)] * hidden[(ihidden, iobserved)];
+            }
+        }
+    }
+    let reconstruction_mean = sum / (200. * 100.);
+    assert!((reconstruction_mean - 50.).abs() < 5.);
+}
+
+#[test]
+#[should_panic(expected = "random Acol needs at least one sample")]
+fn test_random_acol_without_samples() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let samples = Array2D::<FloatT>::zeros((0, 3));
+    RandomAcol { nsamples_averaged: 2 }.initialize(&samples, 2, &mut rng);
+}
+
+#[test]
+#[should_panic(expected = "samples must be finite and nonnegative")]
+fn test_scaled_random_rejects_nan() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut samples = Array2D::<FloatT>::from_elem((3, 2), 1.);
+    samples[(1, 0)] = FloatT::nan();
+    ScaledRandom.initialize(&samples, 2, &mut rng);
+}
+
+#[test]
+fn test_kmeans_finds_clusters() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    // two well separated clusters
+    let mut samples = Array2D::<FloatT>::zeros((6, 2));
+    for isample in 0..3 {
+        samples[(isample, 0)] = 10.;
+        samples[(isample + 3, 1)] = 10.;
+    }
+
+    let (hidden, weights) = KMeans { niterations: 10 }.initialize(&samples, 2, &mut rng);
+    for ihidden in 0..2 {
+        let is_first = 10. == hidden[(ihidden, 0)] && 10. > hidden[(ihidden, 1)];
+        let is_second = 10. == hidden[(ihidden, 1)] && 10. > hidden[(ihidden, 0)];
+        assert!(is_first || is_second);
+    }
+    assert_eq!(weights.shape_as_tuple(), (6, 2));
+}
@@ -18,4 +18,51 @@ mod orthogonal_nmf;
 pub use orthogonal_nmf::OrthogonalNMF;
 
 mod nmf_blas;
-pub use nmf_blas::NMFBlas;
+pub use nmf_blas::{NMFBlas, Divergence, transform, canonicalize};
+
+mod convolutive_nmf;
+pub use convolutive_nmf::ConvolutiveNMF;
+
+pub mod mask;
+pub use mask::{Mask, SparseMask};
+
+pub mod completion;
+
+pub mod sparse;
+pub use sparse::{SparseSamples, SparseNMF};
+
+pub mod nndsvd;
+pub use nndsvd::NNDSVDVariant;
+
+pub mod initializer;
+pub use initializer::Initializer;
+
+pub mod restarts;
+
+pub mod rank_selection;
+
+pub mod evaluation;
+
+pub mod clustering;
+
+pub mod anomaly;
+
+pub mod serialization;
+
+pub mod fit;
+pub use fit::Fit;
+
+pub mod npy;
+
+pub mod delimited;
+pub use delimited::{Labels, LabeledNMF};
+
+pub mod matrix_market;
+
+pub mod text;
+
+pub mod audio;
+
+pub mod patches;
+
+pub mod image_directory;
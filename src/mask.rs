@@ -0,0 +1,206 @@
+/*!
+masks tell the NMF which entries of `samples` are observed.
+
+a mask assigns a weight to each entry of `samples`.
+a weight of `0` means the entry is missing and is excluded
+from the objective and the updates.
+a weight of `1` means the entry is fully observed.
+other nonnegative weights scale the influence of an entry.
+*/
+
+use std::collections::BTreeMap;
+
+use num::{Float, Zero};
+
+use helpers::{Dims, Array2D};
+use nmf_blas::{FloatT, ShapeAsTuple};
+
+pub trait Mask {
+    /// returns the shape of the `samples` this mask belongs to
+    fn mask_shape(&self) -> Dims;
+
+    /// returns the weight of the entry at `index`
+    fn weight(&self, index: Dims) -> FloatT;
+
+    /// `input(i,j) <- input(i,j) * weight(i,j)`.
+    /// entries with weight `0` are set to `0`
+    /// even if they are `NaN` or infinite in `input`.
+    fn apply(&self, input: &mut Array2D<FloatT>);
+}
+
+/// a dense mask holds one weight for every entry of `samples`
+impl Mask for Array2D<FloatT> {
+    #[inline]
+    fn mask_shape(&self) -> Dims {
+        self.shape_as_tuple()
+    }
+
+    #[inline]
+    fn weight(&self, index: Dims) -> FloatT {
+        self[index]
+    }
+
+    fn apply(&self, input: &mut Array2D<FloatT>) {
+        let shape = input.shape_as_tuple();
+        assert_eq!(shape, self.shape_as_tuple());
+
+        for row in 0..shape.0 {
+            for col in 0..shape.1 {
+                let index = (row, col);
+                let weight = unsafe { self.uget(index).clone() };
+                unsafe {
+                    let value = input.uget_mut(index);
+                    // we can't just multiply since `NaN * 0` is `NaN`
+                    if FloatT::zero() == weight {
+                        *value = FloatT::zero();
+                    } else {
+                        *value *= weight;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// returns a dense mask that is `1` where `samples` is finite
+/// and `0` where `samples` is `NaN` or infinite
+pub fn mask_from_finite(samples: &Array2D<FloatT>) -> Array2D<FloatT> {
+    let mut mask = Array2D::<FloatT>::zeros(samples.shape_as_tuple());
+    for (index, value) in samples.indexed_iter() {
+        if value.is_finite() {
+            mask[index] = 1.;
+        }
+    }
+    mask
+}
+
+/// a sparse mask only stores the observed entries.
+/// all entries that are not stored are missing.
+/// use this when only few entries of `samples` are observed.
+#[derive(Clone, Debug)]
+pub struct SparseMask {
+    pub shape: Dims,
+    /// the weight of each observed `(row, col)`.
+    /// ordered such that iterating is deterministic.
+    pub entries: BTreeMap<Dims, FloatT>,
+}
+
+impl SparseMask {
+    /// returns a mask of `shape` where all entries are missing
+    pub fn new(shape: Dims) -> SparseMask {
+        SparseMask {
+            shape: shape,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// returns a mask that observes all finite entries of `samples`
+    pub fn from_finite(samples: &Array2D<FloatT>) -> SparseMask {
+        let mut mask = SparseMask::new(samples.shape_as_tuple());
+        for ((row, col), value) in samples.indexed_iter() {
+            if value.is_finite() {
+                mask.observe(row, col);
+            }
+        }
+        mask
+    }
+
+    /// marks the entry at `(row, col)` as observed
+    #[inline]
+    pub fn observe(&mut self, row: usize, col: usize) {
+        self.observe_with_weight(row, col, 1.);
+    }
+
+    /// marks the entry at `(row, col)` as observed with `weight`.
+    /// replaces the weight if the entry is already observed.
+    pub fn observe_with_weight(&mut self, row: usize, col: usize, weight: FloatT) {
+        assert!(row < self.shape.0);
+        assert!(col < self.shape.1);
+        assert!(FloatT::zero() <= weight);
+        self.entries.insert((row, col), weight);
+    }
+
+    /// returns the number of observed entries
+    #[inline]
+    pub fn nobserved_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// returns the equivalent dense mask
+    pub fn to_dense(&self) -> Array2D<FloatT> {
+        let mut mask = Array2D::<FloatT>::zeros(self.shape);
+        for (&index, &weight) in self.entries.iter() {
+            mask[index] = weight;
+        }
+        mask
+    }
+}
+
+impl Mask for SparseMask {
+    #[inline]
+    fn mask_shape(&self) -> Dims {
+        self.shape
+    }
+
+    #[inline]
+    fn weight(&self, index: Dims) -> FloatT {
+        self.entries.get(&index).cloned().unwrap_or(FloatT::zero())
+    }
+
+    fn apply(&self, input: &mut Array2D<FloatT>) {
+        assert_eq!(self.shape, input.shape_as_tuple());
+
+        // remember the observed values before we zero everything
+        let observed: Vec<FloatT> = self.entries.iter()
+            .map(|(&index, &weight)| input[index] * weight)
+            .collect();
+
+        for x in input.iter_mut() {
+            *x = FloatT::zero();
+        }
+
+        for (&index, value) in self.entries.keys().zip(observed) {
+            input[index] = value;
+        }
+    }
+}
+
+#[test]
+fn test_dense_mask_zeroes_nan() {
+    let mut samples = Array2D::<FloatT>::from_elem((2, 3), 2.);
+    samples[(0, 1)] = FloatT::nan();
+    let mask = mask_from_finite(&samples);
+    assert_eq!(mask.weight((0, 1)), 0.);
+    assert_eq!(mask.weight((1, 1)), 1.);
+    mask.apply(&mut samples);
+    assert_eq!(samples[(0, 1)], 0.);
+    assert_eq!(samples[(0, 0)], 2.);
+}
+
+#[test]
+fn test_sparse_mask_equals_dense_mask() {
+    let mut mask = SparseMask::new((3, 2));
+    mask.observe(0, 0);
+    mask.observe_with_weight(2, 1, 0.5);
+
+    let mut sparse = Array2D::<FloatT>::from_elem((3, 2), 4.);
+    let mut dense = sparse.clone();
+    mask.apply(&mut sparse);
+    mask.to_dense().apply(&mut dense);
+    assert_eq!(sparse, dense);
+    assert_eq!(sparse[(2, 1)], 2.);
+    assert_eq!(sparse[(1, 0)], 0.);
+}
+
+#[test]
+fn test_sparse_mask_observing_twice_replaces_the_weight() {
+    let mut mask = SparseMask::new((2, 2));
+    mask.observe_with_weight(1, 0, 0.5);
+    mask.observe_with_weight(1, 0, 2.);
+    assert_eq!(mask.nobserved_entries(), 1);
+    assert_eq!(mask.weight((1, 0)), 2.);
+
+    let mut samples = Array2D::<FloatT>::from_elem((2, 2), 3.);
+    mask.apply(&mut samples);
+    assert_eq!(samples[(1, 0)], 6.);
+}
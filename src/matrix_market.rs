@@ -0,0 +1,220 @@
+/*!
+read and write Matrix Market (`.mtx`) coordinate files.
+
+coordinate files only list the nonzero entries.
+they are read into `SparseSamples` without densifying.
+one sample per row. one observed variable per column.
+
+supported are the fields `real`, `integer` and `pattern`
+(every listed entry is `1`)
+and the symmetries `general` and `symmetric`.
+see http://math.nist.gov/MatrixMarket/formats.html
+*/
+
+use std;
+use std::io::{Read, Write, BufRead, BufReader};
+use std::fs::File;
+use std::path::Path;
+
+use helpers::Array2D;
+use nmf_blas::FloatT;
+use sparse::SparseSamples;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum MatrixMarketError {
+        /// an error has occured when reading or writing the file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// the file doesn't start with a `%%MatrixMarket` banner
+        NotMatrixMarket {}
+        /// the banner names a format, field or symmetry we don't support
+        Unsupported(what: String) {
+            display("unsupported matrix market {}", what)
+        }
+        /// a line could not be parsed
+        Parse(line: usize, message: String) {
+            display("line {}: {}", line, message)
+        }
+        /// the file has fewer entries than its size line promises
+        MissingEntries(found: usize, expected: usize) {
+            display("found {} entries but expected {}", found, expected)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Real,
+    Pattern,
+}
+
+fn parse_usize(field: Option<&str>, line: usize) -> Result<usize, MatrixMarketError> {
+    field
+        .and_then(|field| field.parse::<usize>().ok())
+        .ok_or_else(|| MatrixMarketError::Parse(line, "expected a nonnegative integer".to_string()))
+}
+
+fn parse_index(field: Option<&str>, size: usize, line: usize) -> Result<usize, MatrixMarketError> {
+    let index = try!(parse_usize(field, line));
+    // indices start at 1
+    if 0 == index || size < index {
+        return Err(MatrixMarketError::Parse(
+            line, format!("index {} is outside of 1..{}", index, size)));
+    }
+    Ok(index - 1)
+}
+
+/// reads a coordinate matrix market file into sparse samples
+pub fn read_mtx<R: Read>(reader: R) -> Result<SparseSamples, MatrixMarketError> {
+    let mut lines = BufReader::new(reader).lines();
+
+    let banner = match lines.next() {
+        Some(line) => try!(line),
+        None => return Err(MatrixMarketError::NotMatrixMarket),
+    };
+    let banner: Vec<String> = banner.split_whitespace().map(|word| word.to_lowercase()).collect();
+    if banner.len() != 5 || banner[0] != "%%matrixmarket" || banner[1] != "matrix" {
+        return Err(MatrixMarketError::NotMatrixMarket);
+    }
+    if banner[2] != "coordinate" {
+        return Err(MatrixMarketError::Unsupported(format!("format `{}`", banner[2])));
+    }
+    let field = match &banner[3][..] {
+        "real" | "integer" => Field::Real,
+        "pattern" => Field::Pattern,
+        other => return Err(MatrixMarketError::Unsupported(format!("field `{}`", other))),
+    };
+    let symmetric = match &banner[4][..] {
+        "general" => false,
+        "symmetric" => true,
+        other => return Err(MatrixMarketError::Unsupported(format!("symmetry `{}`", other))),
+    };
+
+    let mut samples: Option<SparseSamples> = None;
+    let mut nentries = 0;
+    let mut nread = 0;
+
+    for (iline, line) in lines.enumerate() {
+        let line = try!(line);
+        // the banner is line 1
+        let line_number = iline + 2;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+
+        let mut current = match samples.take() {
+            None => {
+                let nrows = try!(parse_usize(fields.next(), line_number));
+                let ncols = try!(parse_usize(fields.next(), line_number));
+                nentries = try!(parse_usize(fields.next(), line_number));
+                if symmetric && nrows != ncols {
+                    return Err(MatrixMarketError::Parse(
+                        line_number, format!("symmetric matrix has {} rows but {} cols", nrows, ncols)));
+                }
+                // no reserve. a corrupt `nentries` shouldn't make us allocate
+                samples = Some(SparseSamples::new((nrows, ncols)));
+                continue;
+            }
+            Some(current) => current,
+        };
+
+        if nread == nentries {
+            return Err(MatrixMarketError::Parse(
+                line_number, format!("more than the {} promised entries", nentries)));
+        }
+        let row = try!(parse_index(fields.next(), current.shape.0, line_number));
+        let col = try!(parse_index(fields.next(), current.shape.1, line_number));
+        let value = match field {
+            Field::Pattern => 1.,
+            Field::Real => try!(fields.next()
+                .and_then(|field| field.parse::<FloatT>().ok())
+                .ok_or_else(|| MatrixMarketError::Parse(line_number, "expected a number".to_string()))),
+        };
+        current.push(row, col, value);
+        // symmetric files only list the lower triangle
+        if symmetric && row != col {
+            current.push(col, row, value);
+        }
+        nread += 1;
+        samples = Some(current);
+    }
+
+    match samples {
+        None => Err(MatrixMarketError::NotMatrixMarket),
+        Some(_) if nread < nentries => Err(MatrixMarketError::MissingEntries(nread, nentries)),
+        Some(samples) => Ok(samples),
+    }
+}
+
+pub fn load_mtx<P: AsRef<Path>>(path: P) -> Result<SparseSamples, MatrixMarketError> {
+    let file = try!(File::open(path));
+    read_mtx(file)
+}
+
+/// writes `samples` as a general real coordinate file
+pub fn write_mtx<W: Write>(writer: &mut W, samples: &SparseSamples) -> std::io::Result<()> {
+    try!(writeln!(writer, "%%MatrixMarket matrix coordinate real general"));
+    try!(writeln!(writer, "{} {} {}", samples.shape.0, samples.shape.1, samples.nstored()));
+    for &(row, col, value) in samples.entries.iter() {
+        try!(writeln!(writer, "{} {} {}", row + 1, col + 1, value));
+    }
+    Ok(())
+}
+
+pub fn save_mtx<P: AsRef<Path>>(path: P, samples: &SparseSamples) -> std::io::Result<()> {
+    let mut file = try!(File::create(path));
+    write_mtx(&mut file, samples)
+}
+
+/// writes the nonzero entries of a dense `matrix` (for example `hidden`)
+/// as a general real coordinate file
+pub fn write_dense_mtx<W: Write>(writer: &mut W, matrix: &Array2D<FloatT>) -> std::io::Result<()> {
+    write_mtx(writer, &SparseSamples::from_dense(matrix))
+}
+
+#[test]
+fn test_read_write_mtx() {
+    let text = "%%MatrixMarket matrix coordinate real general\n\
+                % a comment\n\
+                3 2 2\n\
+                1 2 1.5\n\
+                3 1 -2\n";
+    let samples = read_mtx(text.as_bytes()).unwrap();
+    assert_eq!(samples.shape, (3, 2));
+    assert_eq!(samples.entries, vec![(0, 1, 1.5), (2, 0, -2.)]);
+
+    let mut bytes = Vec::new();
+    write_mtx(&mut bytes, &samples).unwrap();
+    assert_eq!(read_mtx(&bytes[..]).unwrap(), samples);
+}
+
+#[test]
+fn test_read_symmetric_pattern_mtx() {
+    let text = "%%MatrixMarket matrix coordinate pattern symmetric\n2 2 2\n1 1\n2 1\n";
+    let samples = read_mtx(text.as_bytes()).unwrap();
+    assert_eq!(samples.entries, vec![(0, 0, 1.), (1, 0, 1.), (0, 1, 1.)]);
+}
+
+#[test]
+fn test_read_mtx_errors() {
+    match read_mtx("%%MatrixMarket matrix array real general\n".as_bytes()) {
+        Err(MatrixMarketError::Unsupported(_)) => {}
+        other => panic!("expected unsupported but got {:?}", other),
+    }
+    match read_mtx("%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 1\n".as_bytes()) {
+        Err(MatrixMarketError::MissingEntries(1, 2)) => {}
+        other => panic!("expected missing entries but got {:?}", other),
+    }
+    match read_mtx("%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1\n".as_bytes()) {
+        Err(MatrixMarketError::Parse(3, _)) => {}
+        other => panic!("expected a parse error but got {:?}", other),
+    }
+    match read_mtx("%%MatrixMarket matrix coordinate real symmetric\n2 3 1\n2 1 1\n".as_bytes()) {
+        Err(MatrixMarketError::Parse(2, _)) => {}
+        other => panic!("expected a parse error but got {:?}", other),
+    }
+}
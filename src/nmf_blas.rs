@@ -9,7 +9,10 @@ use rblas::attribute::Transpose;
 use ndarray::{ArrayBase, DataOwned, DataMut};
 use ndarray::blas::{BlasArrayViewMut, AsBlas};
 
-use helpers::{random01, Dims, Array2D};
+use helpers::{canonicalize_factors, Array2D};
+use mask::Mask;
+use nndsvd::{nndsvd, NNDSVDVariant};
+use initializer::{Initializer, Random01};
 
 pub trait ShapeAsTuple<T> {
     fn shape_as_tuple(&self) -> T;
@@ -146,6 +149,60 @@ pub fn hidden_divisor(
         &mut hidden_divisor.blas());
 }
 
+/// `weights_divisor <- (mask .* (weights * hidden)) * hidden.transpose()`
+/// `hidden_divisor <- weights.transpose() * (mask .* (weights * hidden))`
+///
+/// both divisors share the masked reconstruction
+/// so it is only computed once.
+#[inline]
+pub fn masked_divisors<M: Mask>(
+    weights: &mut Array2D<FloatT>,
+    hidden: &mut Array2D<FloatT>,
+    mask: &M,
+    // temporary space to hold `mask .* (weights * hidden)`
+    tmp: &mut Array2D<FloatT>,
+    weights_divisor: &mut Array2D<FloatT>,
+    hidden_divisor: &mut Array2D<FloatT>,
+) {
+    assert_eq!(weights.shape()[1], hidden.shape()[0]);
+    assert_eq!(weights.shape()[0], tmp.shape()[0]);
+    assert_eq!(hidden.shape()[1], tmp.shape()[1]);
+    assert_eq!(mask.mask_shape(), tmp.shape_as_tuple());
+
+    // tmp <- weights * hidden
+    Gemm::gemm(
+        &1.,
+        Transpose::NoTrans, &weights.blas(),
+        Transpose::NoTrans, &hidden.blas(),
+        &0.,
+        &mut tmp.blas());
+    // tmp <- mask .* tmp
+    mask.apply(tmp);
+    // weights_divisor <- tmp * hidden.transpose()
+    weights_multiplier(tmp, hidden, weights_divisor);
+    // hidden_divisor <- weights.transpose() * tmp
+    hidden_multiplier(weights, tmp, hidden_divisor);
+}
+
+/// `reconstruction <- weights * hidden`
+#[inline]
+pub fn reconstruction(
+    weights: &mut Array2D<FloatT>,
+    hidden: &mut Array2D<FloatT>,
+    reconstruction: &mut Array2D<FloatT>,
+) {
+    assert_eq!(weights.shape()[1], hidden.shape()[0]);
+    assert_eq!(weights.shape()[0], reconstruction.shape()[0]);
+    assert_eq!(hidden.shape()[1], reconstruction.shape()[1]);
+
+    Gemm::gemm(
+        &1.,
+        Transpose::NoTrans, &weights.blas(),
+        Transpose::NoTrans, &hidden.blas(),
+        &0.,
+        &mut reconstruction.blas());
+}
+
 /// `input_output <- input_output + alpha * gamma * hidden`
 /// #[inline]
 pub fn add_orthogonalization(
@@ -196,6 +253,137 @@ pub fn update_from_multiplier_and_divisor(
     }
 }
 
+/// guards the reconstruction against `0`
+/// in the updates and objectives of the divergences
+pub const DIVERGENCE_EPSILON: FloatT = 1e-9;
+
+/// the objectives `iterate_divergence` can minimize.
+/// all are beta divergences between `samples` and `weights * hidden`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Divergence {
+    /// `(x - y)^2`. beta `2`. the objective of `iterate`.
+    SquaredError,
+    /// `x ln(x / y) - x + y`. beta `1`.
+    /// suits counts and magnitude spectrograms.
+    KullbackLeibler,
+    /// `x / y - ln(x / y) - 1`. beta `0`.
+    /// scale invariant. suits power spectrograms.
+    ItakuraSaito,
+}
+
+impl Divergence {
+    /// returns the beta of the beta divergence
+    pub fn beta(&self) -> FloatT {
+        match *self {
+            Divergence::SquaredError => 2.,
+            Divergence::KullbackLeibler => 1.,
+            Divergence::ItakuraSaito => 0.,
+        }
+    }
+
+    /// returns the divergence of `y` from `x`
+    pub fn of(&self, x: FloatT, y: FloatT) -> FloatT {
+        let y = y.max(DIVERGENCE_EPSILON);
+        match *self {
+            Divergence::SquaredError => (x - y) * (x - y),
+            Divergence::KullbackLeibler => {
+                if FloatT::zero() == x {
+                    y
+                } else {
+                    x * (x / y).ln() - x + y
+                }
+            }
+            Divergence::ItakuraSaito => {
+                let x = x.max(DIVERGENCE_EPSILON);
+                x / y - (x / y).ln() - 1.
+            }
+        }
+    }
+}
+
+/// `numerator <- reconstruction^(beta - 2) .* samples`
+/// `reconstruction <- reconstruction^(beta - 1)`
+///
+/// the matrices the multiplicative updates of the beta divergences
+/// multiply with `hidden` or `weights`.
+/// the second one replaces `reconstruction` to save a temporary.
+fn divergence_terms(
+    divergence: Divergence,
+    samples: &Array2D<FloatT>,
+    reconstruction: &mut Array2D<FloatT>,
+    numerator: &mut Array2D<FloatT>,
+) {
+    assert_eq!(samples.shape_as_tuple(), reconstruction.shape_as_tuple());
+    assert_eq!(samples.shape_as_tuple(), numerator.shape_as_tuple());
+    let beta = divergence.beta();
+    let shape = samples.shape_as_tuple();
+    for row in 0..shape.0 {
+        for col in 0..shape.1 {
+            let index = (row, col);
+            let value = reconstruction[index].max(DIVERGENCE_EPSILON);
+            numerator[index] = value.powf(beta - 2.) * samples[index];
+            reconstruction[index] = value.powf(beta - 1.);
+        }
+    }
+}
+
+/// returns nonnegative `weights` such that `weights * hidden`
+/// approximates `samples` while `hidden` stays fixed.
+/// does `niterations` multiplicative updates of `weights` only.
+/// use this to compute weights for new samples after `hidden` was learned.
+/// `samples` contains one observed per column, one sample per row.
+pub fn transform(
+    hidden: &Array2D<FloatT>,
+    samples: &mut Array2D<FloatT>,
+    niterations: usize,
+) -> Array2D<FloatT> {
+    let (nhidden, nobserved) = hidden.shape_as_tuple();
+    assert_eq!(samples.shape()[1], nobserved);
+    let nsamples = samples.shape()[0];
+    let weights_shape = (nsamples, nhidden);
+
+    let mut hidden = hidden.clone();
+    let mut weights = Array2D::<FloatT>::from_elem(weights_shape, 1.);
+
+    // hidden stays fixed so the multiplier stays fixed as well.
+    // multiplier <- samples * hidden.transpose()
+    let mut multiplier = Array2D::<FloatT>::zeros(weights_shape);
+    weights_multiplier(samples, &mut hidden, &mut multiplier);
+
+    // partial <- hidden * hidden.transpose()
+    let mut partial = Array2D::<FloatT>::zeros((nhidden, nhidden));
+    let mut hidden_copy = hidden.clone();
+    Gemm::gemm(
+        &1.,
+        Transpose::NoTrans, &hidden.blas(),
+        Transpose::Trans, &hidden_copy.blas(),
+        &0.,
+        &mut partial.blas());
+
+    let mut divisor = Array2D::<FloatT>::zeros(weights_shape);
+    for _ in 0..niterations {
+        // divisor <- weights * partial
+        Gemm::gemm(
+            &1.,
+            Transpose::NoTrans, &weights.blas(),
+            Transpose::NoTrans, &partial.blas(),
+            &0.,
+            &mut divisor.blas());
+        update_from_multiplier_and_divisor(&multiplier, &divisor, &mut weights);
+    }
+    weights
+}
+
+/// removes the scale and permutation ambiguity of a factorization
+/// without changing `weights * hidden`.
+/// see `helpers::canonicalize_factors`.
+pub fn canonicalize(hidden: &mut Array2D<FloatT>, weights: &mut Array2D<FloatT>) {
+    let (nhidden, nobserved) = hidden.shape_as_tuple();
+    let nsamples = weights.shape()[0];
+    assert_eq!(weights.shape()[1], nhidden);
+    canonicalize_factors(hidden, weights, nhidden, nobserved, nsamples);
+}
+
 pub struct NMFBlas {
     pub hidden: Array2D<FloatT>,
     pub weights: Array2D<FloatT>,
@@ -211,22 +399,33 @@ pub struct NMFBlas {
     pub hidden_divisor_partial: Array2D<FloatT>,
 
     pub gamma: Array2D<FloatT>,
+
+    // only used by `iterate_divergence`.
+    // allocated by its first call.
+    pub divergence_numerator: Array2D<FloatT>,
 }
 
 impl NMFBlas {
     pub fn new_random01<R: Rng>(
         nhidden: usize, nobserved: usize, nsamples: usize, rng: &mut R) -> NMFBlas {
         // TODO potentially add some assertions
-        let mut hidden = Array2D::<FloatT>::zeros((nhidden, nobserved));
-        for x in hidden.iter_mut() {
-            *x = random01(rng);
-        }
+        let (hidden, weights) = Random01.initialize_shape(nhidden, nobserved, nsamples, rng);
+        Self::new(hidden, weights)
+    }
 
-        let mut weights = Array2D::<FloatT>::zeros((nsamples, nhidden));
-        for x in weights.iter_mut() {
-            *x = random01(rng);
-        }
+    /// lets `initializer` choose the initial `hidden` and `weights`
+    /// for a factorization of `samples` with `nhidden` hidden variables
+    pub fn new_with<I: Initializer, R: Rng>(
+        initializer: &I, samples: &Array2D<FloatT>, nhidden: usize, rng: &mut R) -> NMFBlas {
+        let (hidden, weights) = initializer.initialize(samples, nhidden, rng);
+        Self::new(hidden, weights)
+    }
 
+    /// deterministic initialization from a truncated SVD of `samples`.
+    /// `rng` is only used by `NNDSVDVariant::RandomAverage`.
+    pub fn new_nndsvd<R: Rng>(
+        samples: &Array2D<FloatT>, nhidden: usize, variant: NNDSVDVariant, rng: &mut R) -> NMFBlas {
+        let (hidden, weights) = nndsvd(samples, nhidden, variant, rng);
         Self::new(hidden, weights)
     }
 
@@ -254,6 +453,8 @@ impl NMFBlas {
             hidden_divisor_partial: Array2D::<FloatT>::zeros((nhidden, nhidden)),
 
             gamma: gamma(nhidden),
+
+            divergence_numerator: Array2D::<FloatT>::zeros((0, 0)),
         }
     }
 
@@ -289,6 +490,121 @@ impl NMFBlas {
         (self.nsamples(), self.nobserved())
     }
 
+    /// returns the weights of new `samples` with `hidden` kept fixed.
+    /// neither `hidden` nor `weights` of `self` are changed.
+    /// see the free function `transform`.
+    pub fn transform(
+        &self,
+        samples: &mut Array2D<FloatT>,
+        niterations: usize,
+    ) -> Array2D<FloatT> {
+        transform(&self.hidden, samples, niterations)
+    }
+
+    /// brings `hidden` and `weights` into canonical form
+    /// such that the outputs of different runs are comparable.
+    /// see the free function `canonicalize`.
+    /// hidden variable `0` contributes the most afterwards.
+    #[inline]
+    pub fn canonicalize(&mut self) {
+        canonicalize(&mut self.hidden, &mut self.weights);
+    }
+
+    /// returns the full reconstruction `weights * hidden`
+    /// which has the shape of the samples
+    pub fn reconstruction(&self) -> Array2D<FloatT> {
+        // gemm needs mutable access to its inputs.
+        // copy them like `factors_reconstruction` does
+        // such that all reconstructions take `&self`
+        let mut result = Array2D::<FloatT>::zeros(self.samples_shape());
+        reconstruction(&mut self.weights.clone(), &mut self.hidden.clone(), &mut result);
+        result
+    }
+
+    /// returns the contribution of hidden variable `ihidden` to the samples.
+    /// that is column `ihidden` of `weights` times row `ihidden` of `hidden`.
+    /// the contributions of all hidden variables sum up to `reconstruction`.
+    #[inline]
+    pub fn factor_reconstruction(&self, ihidden: usize) -> Array2D<FloatT> {
+        self.factors_reconstruction(&[ihidden])
+    }
+
+    /// returns the summed contributions of the hidden variables in `ihiddens`
+    /// to the samples
+    pub fn factors_reconstruction(&self, ihiddens: &[usize]) -> Array2D<FloatT> {
+        let nsamples = self.nsamples();
+        let nobserved = self.nobserved();
+        let nselected = ihiddens.len();
+
+        // copy the selected columns of weights and rows of hidden
+        // such that we can multiply them with a single gemm
+        let mut selected_weights = Array2D::<FloatT>::zeros((nsamples, nselected));
+        let mut selected_hidden = Array2D::<FloatT>::zeros((nselected, nobserved));
+        for (iselected, &ihidden) in ihiddens.iter().enumerate() {
+            assert!(ihidden < self.nhidden());
+            for isample in 0..nsamples {
+                selected_weights[(isample, iselected)] = self.weights[(isample, ihidden)];
+            }
+            for iobserved in 0..nobserved {
+                selected_hidden[(iselected, iobserved)] = self.hidden[(ihidden, iobserved)];
+            }
+        }
+
+        let mut result = Array2D::<FloatT>::zeros((nsamples, nobserved));
+        if 0 < nselected {
+            reconstruction(&mut selected_weights, &mut selected_hidden, &mut result);
+        }
+        result
+    }
+
+    /// returns the objective `sum((samples - weights * hidden)^2)`
+    /// that `iterate` minimizes (without orthogonalization).
+    /// lower is better.
+    pub fn squared_error(&self, samples: &Array2D<FloatT>) -> FloatT {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+
+        let reconstruction = self.reconstruction();
+        let mut sum = FloatT::zero();
+        for (value, reconstructed) in samples.iter().zip(reconstruction.iter()) {
+            let difference = value - reconstructed;
+            sum += difference * difference;
+        }
+        sum
+    }
+
+    /// returns for each sample (row of `samples`) the reconstruction error
+    /// `sum((sample - reconstructed sample)^2)`.
+    /// samples the learned factors can't explain have large errors.
+    /// they sum up to `squared_error`.
+    pub fn sample_errors(&mut self, samples: &Array2D<FloatT>) -> Vec<FloatT> {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+
+        reconstruction(
+            &mut self.weights,
+            &mut self.hidden,
+            &mut self.weights_divisor_reconstruction);
+
+        let mut errors = vec![FloatT::zero(); self.nsamples()];
+        for ((isample, iobserved), value) in samples.indexed_iter() {
+            let difference = value - self.weights_divisor_reconstruction[(isample, iobserved)];
+            errors[isample] += difference * difference;
+        }
+        errors
+    }
+
+    /// returns the reconstructed value of `observed` for `sample`.
+    /// that is entry `(sample, observed)` of `weights * hidden`.
+    /// use this to read out entries that were missing in `samples`.
+    pub fn predict(&self, sample: usize, observed: usize) -> FloatT {
+        assert!(sample < self.nsamples());
+        assert!(observed < self.nobserved());
+        let mut sum = FloatT::zero();
+        for ihidden in 0..self.nhidden() {
+            sum += self.weights[(sample, ihidden)] * self.hidden[(ihidden, observed)];
+        }
+        sum
+    }
+
     // TODO consider calling this something like iteration_step
     // TODO how many iterations ?
     // TODO compare this to the seoung solution
@@ -352,4 +668,194 @@ impl NMFBlas {
             &self.hidden_divisor,
             &mut self.hidden);
     }
+
+    /// like `iterate` but only the entries of `samples`
+    /// that have a nonzero weight in `mask` contribute to the updates.
+    /// missing entries of `samples` may hold any value (even `NaN`).
+    /// use this to factorize samples with missing values
+    /// and to complete them afterwards.
+    pub fn iterate_masked<M: Mask>(
+        &mut self,
+        samples: &mut Array2D<FloatT>,
+        mask: &M,
+        orthogonal_with_alpha: Option<FloatT>,
+    ) {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+        assert_eq!(mask.mask_shape(), self.samples_shape());
+
+        // TODO keep this around between iterations to prevent the allocation
+        // masked_samples <- mask .* samples
+        let mut masked_samples = samples.clone();
+        mask.apply(&mut masked_samples);
+
+        // weights_multiplier <- masked_samples * hidden.transpose()
+        weights_multiplier(
+            &mut masked_samples,
+            &mut self.hidden,
+            &mut self.weights_multiplier);
+
+        // hidden_multiplier <- weights.transpose() * masked_samples
+        hidden_multiplier(
+            &mut self.weights,
+            &mut masked_samples,
+            &mut self.hidden_multiplier);
+
+        // weights_divisor <- (mask .* (weights * hidden)) * hidden.transpose()
+        // hidden_divisor <- weights.transpose() * (mask .* (weights * hidden))
+        masked_divisors(
+            &mut self.weights,
+            &mut self.hidden,
+            mask,
+            &mut self.weights_divisor_reconstruction,
+            &mut self.weights_divisor,
+            &mut self.hidden_divisor);
+
+        if let Some(alpha) = orthogonal_with_alpha {
+            // hidden_divisor <- hidden_divisor + alpha * gamma * hidden
+            add_orthogonalization(
+                alpha,
+                &mut self.gamma,
+                &mut self.hidden,
+                &mut self.hidden_divisor);
+        }
+
+        update_from_multiplier_and_divisor(
+            &self.weights_multiplier,
+            &self.weights_divisor,
+            &mut self.weights);
+
+        update_from_multiplier_and_divisor(
+            &self.hidden_multiplier,
+            &self.hidden_divisor,
+            &mut self.hidden);
+    }
+
+    /// like `iterate` but minimizes `divergence` instead of the squared error.
+    /// `samples` must be nonnegative.
+    /// unlike `iterate` this updates `weights` first and then `hidden`
+    /// from the reconstruction with the new `weights`.
+    /// for `Divergence::SquaredError` and `Divergence::KullbackLeibler`
+    /// these updates never increase the divergence.
+    /// for `Divergence::ItakuraSaito` they are the common heuristic
+    /// which usually but not always decreases it.
+    /// because `weights` is updated before `hidden`
+    /// the result differs slightly from that of `iterate`
+    /// even with `Divergence::SquaredError`.
+    pub fn iterate_divergence(
+        &mut self,
+        samples: &Array2D<FloatT>,
+        divergence: Divergence,
+    ) {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+
+        if self.divergence_numerator.shape_as_tuple() != self.samples_shape() {
+            self.divergence_numerator = Array2D::<FloatT>::zeros(self.samples_shape());
+        }
+
+        // weights_divisor_reconstruction <- (weights * hidden)^(beta - 1)
+        // divergence_numerator <- (weights * hidden)^(beta - 2) .* samples
+        reconstruction(
+            &mut self.weights,
+            &mut self.hidden,
+            &mut self.weights_divisor_reconstruction);
+        divergence_terms(
+            divergence,
+            samples,
+            &mut self.weights_divisor_reconstruction,
+            &mut self.divergence_numerator);
+
+        // weights_multiplier <- divergence_numerator * hidden.transpose()
+        weights_multiplier(
+            &mut self.divergence_numerator,
+            &mut self.hidden,
+            &mut self.weights_multiplier);
+        // weights_divisor <- weights_divisor_reconstruction * hidden.transpose()
+        weights_multiplier(
+            &mut self.weights_divisor_reconstruction,
+            &mut self.hidden,
+            &mut self.weights_divisor);
+
+        update_from_multiplier_and_divisor(
+            &self.weights_multiplier,
+            &self.weights_divisor,
+            &mut self.weights);
+
+        // the hidden update uses the reconstruction with the new weights
+        reconstruction(
+            &mut self.weights,
+            &mut self.hidden,
+            &mut self.weights_divisor_reconstruction);
+        divergence_terms(
+            divergence,
+            samples,
+            &mut self.weights_divisor_reconstruction,
+            &mut self.divergence_numerator);
+
+        // hidden_multiplier <- weights.transpose() * divergence_numerator
+        hidden_multiplier(
+            &mut self.weights,
+            &mut self.divergence_numerator,
+            &mut self.hidden_multiplier);
+        // hidden_divisor <- weights.transpose() * weights_divisor_reconstruction
+        hidden_multiplier(
+            &mut self.weights,
+            &mut self.weights_divisor_reconstruction,
+            &mut self.hidden_divisor);
+
+        update_from_multiplier_and_divisor(
+            &self.hidden_multiplier,
+            &self.hidden_divisor,
+            &mut self.hidden);
+    }
+
+    /// returns the objective `sum(divergence(samples, weights * hidden))`
+    /// that `iterate_divergence` minimizes. lower is better.
+    pub fn divergence(&self, samples: &Array2D<FloatT>, divergence: Divergence) -> FloatT {
+        assert_eq!(samples.shape_as_tuple(), self.samples_shape());
+
+        let reconstruction = self.reconstruction();
+        let mut sum = FloatT::zero();
+        for (&value, &reconstructed) in samples.iter().zip(reconstruction.iter()) {
+            sum += divergence.of(value, reconstructed);
+        }
+        sum
+    }
+}
+
+#[test]
+fn test_iterate_divergence_decreases_divergence() {
+    use rand::{StdRng, SeedableRng};
+    use helpers::random01;
+
+    let mut rng: StdRng = SeedableRng::from_seed(&[1, 2, 3][..]);
+    let mut samples = Array2D::<FloatT>::zeros((6, 5));
+    for x in samples.iter_mut() {
+        *x = random01(&mut rng);
+    }
+    for divergence in [Divergence::KullbackLeibler, Divergence::ItakuraSaito].iter() {
+        let mut nmf = NMFBlas::new_random01(2, 5, 6, &mut rng);
+        let before = nmf.divergence(&samples, *divergence);
+        for _ in 0..50 {
+            nmf.iterate_divergence(&samples, *divergence);
+        }
+        assert!(nmf.divergence(&samples, *divergence) < before);
+    }
+}
+
+#[test]
+fn test_canonicalize() {
+    let mut hidden = Array2D::<FloatT>::zeros((2, 2));
+    hidden[(0, 0)] = 2.;
+    hidden[(1, 1)] = 3.;
+    let mut weights = Array2D::<FloatT>::from_elem((3, 2), 1.);
+
+    canonicalize(&mut hidden, &mut weights);
+
+    // the second hidden variable contributes more and comes first now
+    assert_eq!(hidden[(0, 1)], 1.);
+    assert_eq!(hidden[(1, 0)], 1.);
+    for isample in 0..3 {
+        assert_eq!(weights[(isample, 0)], 3.);
+        assert_eq!(weights[(isample, 1)], 2.);
+    }
 }
@@ -0,0 +1,156 @@
+/*!
+nonnegative double singular value decomposition (NNDSVD).
+
+deterministic initialization of `hidden` and `weights`
+from a truncated SVD of the samples as described in
+*SVD based initialization: a head start for nonnegative matrix factorization*
+by Boutsidis and Gallopoulos.
+
+gives reproducible starts and usually faster convergence
+than random initialization.
+*/
+
+use rand::Rng;
+use num::{Float, Zero};
+
+use helpers::{Array2D, truncated_svd, random01};
+use nmf_blas::{FloatT, ShapeAsTuple};
+
+/// number of subspace iterations used for the truncated SVD
+pub const SVD_ITERATIONS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NNDSVDVariant {
+    /// plain NNDSVD. leaves zeros in `hidden` and `weights`.
+    /// multiplicative updates can never change these zeros.
+    /// good for sparse factors.
+    Zeros,
+    /// NNDSVDa. replaces zeros by the mean of the samples
+    Average,
+    /// NNDSVDar. replaces zeros by random values
+    /// between `0` and `mean of the samples / 100`
+    RandomAverage,
+}
+
+/// returns the positive part of `x` and the negated negative part of `x`
+fn split_signs(x: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let positive = x.iter().map(|&v| v.max(0.)).collect();
+    let negative = x.iter().map(|&v| (-v).max(0.)).collect();
+    (positive, negative)
+}
+
+fn norm(x: &[f64]) -> f64 {
+    x.iter().fold(0., |sum, v| sum + v * v).sqrt()
+}
+
+/// returns `(hidden, weights)` for `nhidden` hidden variables
+/// initialized from `samples` with NNDSVD.
+/// `samples` contains one observed per column, one sample per row.
+/// `rng` is only used by `NNDSVDVariant::RandomAverage`.
+pub fn nndsvd<R: Rng>(
+    samples: &Array2D<FloatT>,
+    nhidden: usize,
+    variant: NNDSVDVariant,
+    rng: &mut R,
+) -> (Array2D<FloatT>, Array2D<FloatT>) {
+    let (nsamples, nobserved) = samples.shape_as_tuple();
+    assert!(0 < nhidden);
+    assert!(nhidden <= nsamples && nhidden <= nobserved,
+            "NNDSVD needs nhidden <= min(nsamples, nobserved)");
+
+    // compute the SVD in f64 for numerical stability
+    let mut samples64 = Array2D::<f64>::zeros((nsamples, nobserved));
+    for (index, value) in samples.indexed_iter() {
+        assert!(FloatT::zero() <= *value, "samples must be nonnegative");
+        samples64[index] = *value as f64;
+    }
+    let svd = truncated_svd(&samples64, nhidden, SVD_ITERATIONS);
+
+    let mut hidden = Array2D::<FloatT>::zeros((nhidden, nobserved));
+    let mut weights = Array2D::<FloatT>::zeros((nsamples, nhidden));
+
+    for j in 0..nhidden {
+        let u: Vec<f64> = (0..nsamples).map(|i| svd.u[(i, j)]).collect();
+        let v: Vec<f64> = (0..nobserved).map(|i| svd.v[(j, i)]).collect();
+
+        let (u, v, sigma) = if 0 == j {
+            // the leading singular vectors of a nonnegative matrix
+            // can be chosen nonnegative
+            let u: Vec<f64> = u.iter().map(|x| x.abs()).collect();
+            let v: Vec<f64> = v.iter().map(|x| x.abs()).collect();
+            (u, v, 1.)
+        } else {
+            let (u_positive, u_negative) = split_signs(&u[..]);
+            let (v_positive, v_negative) = split_signs(&v[..]);
+            let u_positive_norm = norm(&u_positive[..]);
+            let v_positive_norm = norm(&v_positive[..]);
+            let u_negative_norm = norm(&u_negative[..]);
+            let v_negative_norm = norm(&v_negative[..]);
+            let positive = u_positive_norm * v_positive_norm;
+            let negative = u_negative_norm * v_negative_norm;
+            // keep the dominating sign
+            if negative < positive {
+                (u_positive.iter().map(|x| x / u_positive_norm).collect(),
+                 v_positive.iter().map(|x| x / v_positive_norm).collect(),
+                 positive)
+            } else if 0. < negative {
+                (u_negative.iter().map(|x| x / u_negative_norm).collect(),
+                 v_negative.iter().map(|x| x / v_negative_norm).collect(),
+                 negative)
+            } else {
+                (vec![0.; nsamples], vec![0.; nobserved], 0.)
+            }
+        };
+
+        let scale = (svd.s[j] * sigma).sqrt();
+        for i in 0..nsamples {
+            weights[(i, j)] = (scale * u[i]) as FloatT;
+        }
+        for i in 0..nobserved {
+            hidden[(j, i)] = (scale * v[i]) as FloatT;
+        }
+    }
+
+    if variant != NNDSVDVariant::Zeros {
+        let mean = samples.iter().fold(0., |sum, x| sum + *x as f64)
+            / (nsamples * nobserved) as f64;
+        let mean = mean as FloatT;
+        for x in hidden.iter_mut().chain(weights.iter_mut()) {
+            if FloatT::zero() == *x {
+                *x = match variant {
+                    NNDSVDVariant::RandomAverage => {
+                        let random: FloatT = random01(rng);
+                        random * mean / 100.
+                    }
+                    _ => mean,
+                };
+            }
+        }
+    }
+
+    (hidden, weights)
+}
+
+#[test]
+fn test_nndsvd_is_nonnegative_and_reproducible() {
+    use rand::{StdRng, SeedableRng};
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut samples = Array2D::<FloatT>::zeros((6, 5));
+    for x in samples.iter_mut() {
+        *x = random01(&mut rng);
+    }
+
+    let (hidden, weights) = nndsvd(&samples, 3, NNDSVDVariant::Average, &mut rng);
+    assert_eq!(hidden.shape_as_tuple(), (3, 5));
+    assert_eq!(weights.shape_as_tuple(), (6, 3));
+    for x in hidden.iter().chain(weights.iter()) {
+        assert!(FloatT::zero() < *x);
+    }
+
+    let (hidden_again, weights_again) = nndsvd(&samples, 3, NNDSVDVariant::Average, &mut rng);
+    assert_eq!(hidden, hidden_again);
+    assert_eq!(weights, weights_again);
+}
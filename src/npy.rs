@@ -0,0 +1,620 @@
+/*!
+read and write matrices in the NumPy `.npy` format
+and `.npz` archives of several `.npy` files.
+
+only two dimensional arrays of `f32` (`<f4`) and `f64` (`<f8`) are supported.
+both C (row major) and Fortran (column major) order are read.
+C order is written.
+an `f4` array can be read as `f64`.
+any other dtype mismatch is an error.
+
+`.npz` archives written by `numpy.savez` are read.
+archives written by `numpy.savez_compressed` are not
+since reading them requires a deflate implementation.
+*/
+
+use std;
+use std::io::{Read, Write};
+use std::fs::File;
+use std::path::Path;
+use std::mem;
+
+use nalgebra::DMat;
+
+use helpers::{Array2D, array_to_dmat, dmat_to_array};
+use nmf_blas::ShapeAsTuple;
+
+pub const NPY_MAGIC: &'static [u8] = b"\x93NUMPY";
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum NpyError {
+        /// an error has occured when reading or writing the file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// the data is not in the `.npy` or `.npz` format
+        Format(message: String) {
+            display("invalid format: {}", message)
+        }
+        /// the dtype of the data can't be read into the requested element type
+        Dtype(found: String, expected: &'static str) {
+            display("can't read dtype `{}` as `{}`", found, expected)
+        }
+        /// the array does not have two dimensions
+        Dimensions(ndim: usize) {
+            display("expected an array with 2 dimensions but found {}", ndim)
+        }
+        /// the `.npz` archive uses a feature that is not supported
+        Unsupported(message: String) {
+            display("unsupported: {}", message)
+        }
+        /// there is no array with this name in the `.npz` archive
+        Missing(name: String) {
+            display("no array named `{}` in archive", name)
+        }
+    }
+}
+
+/// element types that can be read from and written to `.npy` files
+pub trait NpyElement: Sized + Clone + Copy + Default {
+    /// the little endian numpy dtype string of `Self`
+    fn descr() -> &'static str;
+
+    /// returns the number of bytes of one value of the dtype `descr`
+    /// or `None` if `descr` can't be read as `Self`.
+    fn value_size(descr: &str) -> Option<usize>;
+
+    /// reads one value of the dtype `descr` or returns `None`
+    /// if `descr` can't be read as `Self`.
+    fn read_value<R: Read>(reader: &mut R, descr: &str) -> Option<std::io::Result<Self>>;
+
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<()>;
+
+    fn zero() -> Self;
+}
+
+fn read_bytes<R: Read>(reader: &mut R, count: usize, big_endian: bool) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    try!(reader.read_exact(&mut bytes[..count]));
+    let mut value = 0u64;
+    for i in 0..count {
+        let byte = if big_endian { bytes[i] } else { bytes[count - 1 - i] };
+        value = (value << 8) | byte as u64;
+    }
+    Ok(value)
+}
+
+fn write_bytes<W: Write>(writer: &mut W, value: u64, count: usize) -> std::io::Result<()> {
+    let mut bytes = [0u8; 8];
+    for i in 0..count {
+        bytes[i] = (value >> (8 * i)) as u8;
+    }
+    writer.write_all(&bytes[..count])
+}
+
+fn read_f32<R: Read>(reader: &mut R, big_endian: bool) -> std::io::Result<f32> {
+    let bits = try!(read_bytes(reader, 4, big_endian)) as u32;
+    Ok(unsafe { mem::transmute::<u32, f32>(bits) })
+}
+
+fn read_f64<R: Read>(reader: &mut R, big_endian: bool) -> std::io::Result<f64> {
+    let bits = try!(read_bytes(reader, 8, big_endian));
+    Ok(unsafe { mem::transmute::<u64, f64>(bits) })
+}
+
+impl NpyElement for f32 {
+    fn descr() -> &'static str { "<f4" }
+
+    fn value_size(descr: &str) -> Option<usize> {
+        match descr {
+            "<f4" | "=f4" | ">f4" => Some(4),
+            _ => None,
+        }
+    }
+
+    fn read_value<R: Read>(reader: &mut R, descr: &str) -> Option<std::io::Result<f32>> {
+        match descr {
+            "<f4" | "=f4" => Some(read_f32(reader, false)),
+            ">f4" => Some(read_f32(reader, true)),
+            _ => None,
+        }
+    }
+
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write_bytes(writer, unsafe { mem::transmute::<f32, u32>(*self) } as u64, 4)
+    }
+
+    fn zero() -> f32 { 0. }
+}
+
+impl NpyElement for f64 {
+    fn descr() -> &'static str { "<f8" }
+
+    fn value_size(descr: &str) -> Option<usize> {
+        match descr {
+            "<f8" | "=f8" | ">f8" => Some(8),
+            "<f4" | "=f4" | ">f4" => Some(4),
+            _ => None,
+        }
+    }
+
+    fn read_value<R: Read>(reader: &mut R, descr: &str) -> Option<std::io::Result<f64>> {
+        match descr {
+            "<f8" | "=f8" => Some(read_f64(reader, false)),
+            ">f8" => Some(read_f64(reader, true)),
+            // widening is lossless
+            "<f4" | "=f4" => Some(read_f32(reader, false).map(|x| x as f64)),
+            ">f4" => Some(read_f32(reader, true).map(|x| x as f64)),
+            _ => None,
+        }
+    }
+
+    fn write_value<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write_bytes(writer, unsafe { mem::transmute::<f64, u64>(*self) }, 8)
+    }
+
+    fn zero() -> f64 { 0. }
+}
+
+/// the parsed header of a `.npy` file
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyHeader {
+    pub descr: String,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>,
+}
+
+/// returns the text after `'key':` in the header dict
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let pattern = format!("'{}':", key);
+    match header.find(&pattern[..]) {
+        Some(start) => Ok(header[start + pattern.len()..].trim_left()),
+        None => Err(NpyError::Format(format!("header is missing `{}`", key))),
+    }
+}
+
+/// parses a header like
+/// `{'descr': '<f4', 'fortran_order': False, 'shape': (3, 4), }`
+pub fn parse_header(header: &str) -> Result<NpyHeader, NpyError> {
+    let descr = try!(header_value(header, "descr"));
+    let descr = match descr.chars().next() {
+        Some(quote) if quote == '\'' || quote == '"' => {
+            match descr[1..].find(quote) {
+                Some(end) => descr[1..end + 1].to_string(),
+                None => return Err(NpyError::Format("unterminated descr".to_string())),
+            }
+        }
+        _ => return Err(NpyError::Format("descr is not a string".to_string())),
+    };
+
+    let fortran_order = try!(header_value(header, "fortran_order"));
+    let fortran_order = if fortran_order.starts_with("True") {
+        true
+    } else if fortran_order.starts_with("False") {
+        false
+    } else {
+        return Err(NpyError::Format("fortran_order is not a bool".to_string()));
+    };
+
+    let shape = try!(header_value(header, "shape"));
+    if !shape.starts_with("(") {
+        return Err(NpyError::Format("shape is not a tuple".to_string()));
+    }
+    let end = match shape.find(')') {
+        Some(end) => end,
+        None => return Err(NpyError::Format("unterminated shape".to_string())),
+    };
+    let mut dims = Vec::new();
+    for dim in shape[1..end].split(',').map(|dim| dim.trim()).filter(|dim| !dim.is_empty()) {
+        match dim.trim_right_matches('L').parse::<usize>() {
+            Ok(dim) => dims.push(dim),
+            Err(_) => return Err(NpyError::Format(format!("invalid dimension `{}`", dim))),
+        }
+    }
+
+    Ok(NpyHeader {
+        descr: descr,
+        fortran_order: fortran_order,
+        shape: dims,
+    })
+}
+
+/// reads a two dimensional array from `.npy` data
+pub fn read_npy<T: NpyElement, R: Read>(reader: &mut R) -> Result<Array2D<T>, NpyError> {
+    let mut magic = [0u8; 6];
+    try!(reader.read_exact(&mut magic));
+    if &magic[..] != NPY_MAGIC {
+        return Err(NpyError::Format("missing magic string".to_string()));
+    }
+    let mut version = [0u8; 2];
+    try!(reader.read_exact(&mut version));
+    let header_len = match version[0] {
+        1 => try!(read_bytes(reader, 2, false)) as usize,
+        2 | 3 => try!(read_bytes(reader, 4, false)) as usize,
+        major => return Err(NpyError::Unsupported(format!("npy version {}", major))),
+    };
+    // `take` such that a corrupt `header_len` can't make us allocate
+    let mut header = Vec::new();
+    try!(reader.take(header_len as u64).read_to_end(&mut header));
+    if header.len() < header_len {
+        return Err(NpyError::Format("truncated header".to_string()));
+    }
+    let header = match String::from_utf8(header) {
+        Ok(header) => header,
+        Err(_) => return Err(NpyError::Format("header is not utf8".to_string())),
+    };
+    let header = try!(parse_header(&header[..]));
+
+    if 2 != header.shape.len() {
+        return Err(NpyError::Dimensions(header.shape.len()));
+    }
+    let (nrows, ncols) = (header.shape[0], header.shape[1]);
+
+    // checked before the element count such that
+    // arrays without elements report a mismatch as well
+    let value_size = match T::value_size(&header.descr[..]) {
+        Some(value_size) => value_size,
+        None => return Err(NpyError::Dtype(header.descr.clone(), T::descr())),
+    };
+
+    // check the shape against the payload before allocating the array
+    let mut payload = Vec::new();
+    try!(reader.read_to_end(&mut payload));
+    let fits = nrows.checked_mul(ncols)
+        .and_then(|nvalues| nvalues.checked_mul(value_size))
+        .map(|nbytes| nbytes <= payload.len())
+        .unwrap_or(false);
+    if !fits {
+        return Err(NpyError::Format(format!(
+            "shape ({}, {}) doesn't fit into the {} bytes of data", nrows, ncols, payload.len())));
+    }
+    let reader = &mut &payload[..];
+
+    let mut array = Array2D::<T>::from_elem((nrows, ncols), T::zero());
+    let (nouter, ninner) = if header.fortran_order { (ncols, nrows) } else { (nrows, ncols) };
+    for outer in 0..nouter {
+        for inner in 0..ninner {
+            let value = match T::read_value(reader, &header.descr[..]) {
+                Some(value) => try!(value),
+                None => return Err(NpyError::Dtype(header.descr.clone(), T::descr())),
+            };
+            let index = if header.fortran_order { (inner, outer) } else { (outer, inner) };
+            array[index] = value;
+        }
+    }
+    Ok(array)
+}
+
+/// writes `array` as version 1.0 `.npy` data in C order
+pub fn write_npy<T: NpyElement, W: Write>(writer: &mut W, array: &Array2D<T>) -> std::io::Result<()> {
+    let (nrows, ncols) = array.shape_as_tuple();
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+        T::descr(), nrows, ncols);
+    // pad such that the data starts at a multiple of 64 bytes.
+    // magic (6) + version (2) + header length (2) + header + newline
+    let unpadded = NPY_MAGIC.len() + 2 + 2 + header.len() + 1;
+    let padding = (64 - unpadded % 64) % 64;
+    for _ in 0..padding {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    try!(writer.write_all(NPY_MAGIC));
+    try!(writer.write_all(&[1, 0]));
+    try!(write_bytes(writer, header.len() as u64, 2));
+    try!(writer.write_all(header.as_bytes()));
+    for row in 0..nrows {
+        for col in 0..ncols {
+            try!(array[(row, col)].write_value(writer));
+        }
+    }
+    Ok(())
+}
+
+pub fn load_npy<T: NpyElement, P: AsRef<Path>>(path: P) -> Result<Array2D<T>, NpyError> {
+    let mut file = std::io::BufReader::new(try!(File::open(path)));
+    read_npy(&mut file)
+}
+
+pub fn save_npy<T: NpyElement, P: AsRef<Path>>(path: P, array: &Array2D<T>) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(try!(File::create(path)));
+    write_npy(&mut file, array)
+}
+
+pub fn load_npy_dmat<T: NpyElement, P: AsRef<Path>>(path: P) -> Result<DMat<T>, NpyError> {
+    load_npy(path).map(|array| array_to_dmat(&array))
+}
+
+pub fn save_npy_dmat<T: NpyElement, P: AsRef<Path>>(path: P, matrix: &DMat<T>) -> std::io::Result<()> {
+    save_npy(path, &dmat_to_array(matrix))
+}
+
+// `.npz` archives are zip files with one `.npy` file per array.
+
+const ZIP_LOCAL_HEADER: u64 = 0x04034b50;
+const ZIP_CENTRAL_HEADER: u64 = 0x02014b50;
+const ZIP_END_OF_CENTRAL_DIRECTORY: u64 = 0x06054b50;
+
+/// crc32 as used by zip
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for i in 0..256 {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if 0 != c & 1 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        table[i] = c;
+    }
+    let mut crc = 0xffffffffu32;
+    for &byte in data.iter() {
+        crc = table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffffffff
+}
+
+fn le(data: &[u8], offset: usize, count: usize) -> Result<u64, NpyError> {
+    if data.len() < offset + count {
+        return Err(NpyError::Format("truncated zip archive".to_string()));
+    }
+    let mut value = 0u64;
+    for i in 0..count {
+        value |= (data[offset + i] as u64) << (8 * i);
+    }
+    Ok(value)
+}
+
+/// reads all arrays from `.npz` data.
+/// returns them in archive order with their names (without `.npy`).
+pub fn read_npz<T: NpyElement, R: Read>(reader: &mut R) -> Result<Vec<(String, Array2D<T>)>, NpyError> {
+    let mut data = Vec::new();
+    try!(reader.read_to_end(&mut data));
+
+    // the end of central directory record is at least 22 bytes
+    // and followed by a comment of at most 65535 bytes
+    if data.len() < 22 {
+        return Err(NpyError::Format("not a zip archive".to_string()));
+    }
+    let mut end = None;
+    let lowest = if data.len() > 22 + 65535 { data.len() - 22 - 65535 } else { 0 };
+    let mut offset = data.len() - 22;
+    loop {
+        if ZIP_END_OF_CENTRAL_DIRECTORY == try!(le(&data, offset, 4)) {
+            end = Some(offset);
+            break;
+        }
+        if offset == lowest {
+            break;
+        }
+        offset -= 1;
+    }
+    let end = match end {
+        Some(end) => end,
+        None => return Err(NpyError::Format("not a zip archive".to_string())),
+    };
+
+    let nentries = try!(le(&data, end + 10, 2)) as usize;
+    let mut offset = try!(le(&data, end + 16, 4)) as usize;
+    if 0xffff == nentries || 0xffffffff == offset {
+        return Err(NpyError::Unsupported("zip64 archives".to_string()));
+    }
+
+    let mut arrays = Vec::with_capacity(nentries);
+    for _ in 0..nentries {
+        if ZIP_CENTRAL_HEADER != try!(le(&data, offset, 4)) {
+            return Err(NpyError::Format("invalid central directory".to_string()));
+        }
+        let method = try!(le(&data, offset + 10, 2));
+        let compressed_size = try!(le(&data, offset + 20, 4)) as usize;
+        let name_len = try!(le(&data, offset + 28, 2)) as usize;
+        let extra_len = try!(le(&data, offset + 30, 2)) as usize;
+        let comment_len = try!(le(&data, offset + 32, 2)) as usize;
+        let local_offset = try!(le(&data, offset + 42, 4)) as usize;
+        if data.len() < offset + 46 + name_len {
+            return Err(NpyError::Format("truncated zip archive".to_string()));
+        }
+        let name = String::from_utf8_lossy(&data[offset + 46..offset + 46 + name_len]).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if 0 != method {
+            return Err(NpyError::Unsupported(format!(
+                "compressed entry `{}` (use `numpy.savez` instead of `numpy.savez_compressed`)",
+                name)));
+        }
+
+        if ZIP_LOCAL_HEADER != try!(le(&data, local_offset, 4)) {
+            return Err(NpyError::Format("invalid local file header".to_string()));
+        }
+        let local_name_len = try!(le(&data, local_offset + 26, 2)) as usize;
+        let local_extra_len = try!(le(&data, local_offset + 28, 2)) as usize;
+        let start = local_offset + 30 + local_name_len + local_extra_len;
+        if data.len() < start + compressed_size {
+            return Err(NpyError::Format("truncated zip archive".to_string()));
+        }
+
+        let array = try!(read_npy(&mut &data[start..start + compressed_size]));
+        let name = if name.ends_with(".npy") {
+            name[..name.len() - 4].to_string()
+        } else {
+            name
+        };
+        arrays.push((name, array));
+    }
+    Ok(arrays)
+}
+
+/// writes `arrays` as an uncompressed `.npz` archive
+/// that `numpy.load` can read.
+/// each array is stored as `name.npy`.
+pub fn write_npz<T: NpyElement, W: Write>(
+    writer: &mut W, arrays: &[(&str, &Array2D<T>)],
+) -> std::io::Result<()> {
+    let mut offset = 0u64;
+    let mut central_directory = Vec::new();
+
+    for &(name, array) in arrays.iter() {
+        let name = format!("{}.npy", name);
+        let mut npy = Vec::new();
+        try!(write_npy(&mut npy, array));
+        let crc = crc32(&npy[..]) as u64;
+        let size = npy.len() as u64;
+
+        let mut local = Vec::new();
+        try!(write_bytes(&mut local, ZIP_LOCAL_HEADER, 4));
+        try!(write_bytes(&mut local, 20, 2)); // version needed
+        try!(write_bytes(&mut local, 0, 2)); // flags
+        try!(write_bytes(&mut local, 0, 2)); // stored
+        try!(write_bytes(&mut local, 0, 2)); // time
+        try!(write_bytes(&mut local, 0x21, 2)); // date: 1980-01-01
+        try!(write_bytes(&mut local, crc, 4));
+        try!(write_bytes(&mut local, size, 4));
+        try!(write_bytes(&mut local, size, 4));
+        try!(write_bytes(&mut local, name.len() as u64, 2));
+        try!(write_bytes(&mut local, 0, 2)); // extra length
+        try!(local.write_all(name.as_bytes()));
+
+        try!(write_bytes(&mut central_directory, ZIP_CENTRAL_HEADER, 4));
+        try!(write_bytes(&mut central_directory, 20, 2)); // version made by
+        try!(write_bytes(&mut central_directory, 20, 2)); // version needed
+        try!(write_bytes(&mut central_directory, 0, 2)); // flags
+        try!(write_bytes(&mut central_directory, 0, 2)); // stored
+        try!(write_bytes(&mut central_directory, 0, 2)); // time
+        try!(write_bytes(&mut central_directory, 0x21, 2)); // date
+        try!(write_bytes(&mut central_directory, crc, 4));
+        try!(write_bytes(&mut central_directory, size, 4));
+        try!(write_bytes(&mut central_directory, size, 4));
+        try!(write_bytes(&mut central_directory, name.len() as u64, 2));
+        try!(write_bytes(&mut central_directory, 0, 2)); // extra length
+        try!(write_bytes(&mut central_directory, 0, 2)); // comment length
+        try!(write_bytes(&mut central_directory, 0, 2)); // disk number
+        try!(write_bytes(&mut central_directory, 0, 2)); // internal attributes
+        try!(write_bytes(&mut central_directory, 0, 4)); // external attributes
+        try!(write_bytes(&mut central_directory, offset, 4));
+        try!(central_directory.write_all(name.as_bytes()));
+
+        try!(writer.write_all(&local[..]));
+        try!(writer.write_all(&npy[..]));
+        offset += local.len() as u64 + size;
+    }
+
+    try!(writer.write_all(&central_directory[..]));
+    let mut end = Vec::new();
+    try!(write_bytes(&mut end, ZIP_END_OF_CENTRAL_DIRECTORY, 4));
+    try!(write_bytes(&mut end, 0, 2)); // disk number
+    try!(write_bytes(&mut end, 0, 2)); // disk with central directory
+    try!(write_bytes(&mut end, arrays.len() as u64, 2));
+    try!(write_bytes(&mut end, arrays.len() as u64, 2));
+    try!(write_bytes(&mut end, central_directory.len() as u64, 4));
+    try!(write_bytes(&mut end, offset, 4));
+    try!(write_bytes(&mut end, 0, 2)); // comment length
+    writer.write_all(&end[..])
+}
+
+pub fn load_npz<T: NpyElement, P: AsRef<Path>>(path: P) -> Result<Vec<(String, Array2D<T>)>, NpyError> {
+    let mut file = try!(File::open(path));
+    read_npz(&mut file)
+}
+
+/// returns the array called `name` from the `.npz` archive at `path`
+pub fn load_npz_array<T: NpyElement, P: AsRef<Path>>(path: P, name: &str) -> Result<Array2D<T>, NpyError> {
+    let arrays = try!(load_npz(path));
+    match arrays.into_iter().find(|&(ref array_name, _)| array_name == name) {
+        Some((_, array)) => Ok(array),
+        None => Err(NpyError::Missing(name.to_string())),
+    }
+}
+
+pub fn save_npz<T: NpyElement, P: AsRef<Path>>(path: P, arrays: &[(&str, &Array2D<T>)]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(try!(File::create(path)));
+    write_npz(&mut file, arrays)
+}
+
+#[test]
+fn test_npy_roundtrip() {
+    let mut array = Array2D::<f32>::zeros((2, 3));
+    for (i, x) in array.iter_mut().enumerate() {
+        *x = i as f32 / 4.;
+    }
+    let mut bytes = Vec::new();
+    write_npy(&mut bytes, &array).unwrap();
+    assert_eq!(0, (bytes.len() - 6 * 4) % 64);
+    let loaded: Array2D<f32> = read_npy(&mut &bytes[..]).unwrap();
+    assert_eq!(array, loaded);
+
+    // f4 can be read as f8
+    let widened: Array2D<f64> = read_npy(&mut &bytes[..]).unwrap();
+    assert_eq!(widened[(1, 2)], 1.25);
+}
+
+#[test]
+fn test_npy_fortran_order() {
+    let mut bytes = Vec::new();
+    let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }\n";
+    bytes.extend(NPY_MAGIC.iter().cloned());
+    bytes.extend([1u8, 0].iter().cloned());
+    write_bytes(&mut bytes, header.len() as u64, 2).unwrap();
+    bytes.extend(header.as_bytes().iter().cloned());
+    // column major: (0,0) (1,0) (0,1) (1,1) (0,2) (1,2)
+    for &x in [0., 3., 1., 4., 2., 5.].iter() {
+        (x as f64).write_value(&mut bytes).unwrap();
+    }
+    let array: Array2D<f64> = read_npy(&mut &bytes[..]).unwrap();
+    assert_eq!(array[(0, 1)], 1.);
+    assert_eq!(array[(1, 0)], 3.);
+    assert_eq!(array[(1, 2)], 5.);
+}
+
+#[test]
+fn test_npy_dtype_mismatch() {
+    let array = Array2D::<f64>::zeros((1, 1));
+    let mut bytes = Vec::new();
+    write_npy(&mut bytes, &array).unwrap();
+    match read_npy::<f32, _>(&mut &bytes[..]) {
+        Err(NpyError::Dtype(ref found, "<f4")) if found == "<f8" => {}
+        other => panic!("expected a dtype error but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_npy_empty_dtype_mismatch() {
+    let array = Array2D::<f64>::zeros((0, 3));
+    let mut bytes = Vec::new();
+    write_npy(&mut bytes, &array).unwrap();
+    match read_npy::<f32, _>(&mut &bytes[..]) {
+        Err(NpyError::Dtype(ref found, "<f4")) if found == "<f8" => {}
+        other => panic!("expected a dtype error but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_npy_shape_larger_than_payload() {
+    let mut bytes = Vec::new();
+    let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (9999999999, 3), }\n";
+    bytes.extend(NPY_MAGIC.iter().cloned());
+    bytes.extend([1u8, 0].iter().cloned());
+    write_bytes(&mut bytes, header.len() as u64, 2).unwrap();
+    bytes.extend(header.as_bytes().iter().cloned());
+    for _ in 0..3 {
+        (1. as f32).write_value(&mut bytes).unwrap();
+    }
+    match read_npy::<f32, _>(&mut &bytes[..]) {
+        Err(NpyError::Format(_)) => {}
+        other => panic!("expected a format error but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_npz_roundtrip() {
+    let a = Array2D::<f64>::from_elem((2, 2), 1.5);
+    let b = Array2D::<f64>::from_elem((1, 3), 2.5);
+    let mut bytes = Vec::new();
+    write_npz(&mut bytes, &[("hidden", &a), ("weights", &b)]).unwrap();
+    let arrays: Vec<(String, Array2D<f64>)> = read_npz(&mut &bytes[..]).unwrap();
+    assert_eq!(arrays, vec![("hidden".to_string(), a), ("weights".to_string(), b)]);
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+}
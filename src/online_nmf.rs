@@ -4,10 +4,22 @@ into ...
 
 use it by repeatedly calling `update` with data
 and inspecting `hidden` and `weights`.
+
+`update` does not learn yet.
+`hidden` stays what it was set to.
+assign a `hidden` learned elsewhere (for example with `NMFBlas`,
+`new_with` or loaded with `from_saved`) before relying on `column_errors`.
 */
 
-use num::{Zero};
-use nalgebra::{DMat};
+use std::ops::Mul;
+
+use num::{Zero, Float};
+use nalgebra::{DMat, Transpose};
+
+use rand::Rng;
+
+use initializer::{Initializer, initialize_dmat};
+use orthogonal_nmf::transform;
 
 pub struct OnlineNMF<FloatT> {
     /// maps hidden variables (one per row) to observed variables (one per column).
@@ -50,9 +62,54 @@ impl<FloatT: Zero + Clone + Copy> OnlineNMF<FloatT> {
         self.weights.nrows()
     }
 
+    /// does not change `hidden` or `weights` yet.
+    /// see the module documentation.
     pub fn update(&mut self, new_observed_columns: &DMat<FloatT>) {
         assert_eq!(self.nobserved(), new_observed_columns.nrows());
 
-        // do nothing for now
+        // TODO learn `hidden` and append to `weights`
+    }
+}
+
+impl<FloatT: Float> OnlineNMF<FloatT> {
+    /// lets `initializer` choose the initial `hidden` and `weights`
+    /// from the first `observed_columns` (one time per column)
+    /// with `nhidden` hidden variables.
+    /// `weights` gets one row per column of `observed_columns`.
+    pub fn new_with<I: Initializer, R: Rng>(
+        initializer: &I, observed_columns: &DMat<FloatT>, nhidden: usize, rng: &mut R) -> OnlineNMF<FloatT> {
+        // one time per row like `weights`
+        let samples = observed_columns.transpose();
+        let (hidden, weights) = initialize_dmat(initializer, &samples, nhidden, rng);
+        OnlineNMF {
+            hidden: hidden,
+            weights: weights,
+        }
+    }
+
+    /// returns for each column of `new_observed_columns`
+    /// the squared error of its best reconstruction from the current `hidden`.
+    /// the weights of the reconstruction are found with
+    /// `niterations` multiplicative updates and `hidden` kept fixed.
+    /// call this on each new batch before passing it to `update`
+    /// and feed the result into an `anomaly::AnomalyDetector`.
+    /// since `update` does not learn yet the errors are relative
+    /// to whatever `hidden` was set to.
+    pub fn column_errors(&self, new_observed_columns: &DMat<FloatT>, niterations: usize) -> Vec<FloatT> {
+        assert_eq!(self.nobserved(), new_observed_columns.nrows());
+
+        // one sample per row like `hidden`
+        let samples = new_observed_columns.transpose();
+        let weights = transform(&self.hidden, &samples, niterations);
+
+        let reconstruction = weights.mul(&self.hidden);
+        let mut errors = vec![FloatT::zero(); samples.nrows()];
+        for col in 0..samples.ncols() {
+            for row in 0..samples.nrows() {
+                let difference = samples[(row, col)] - reconstruction[(row, col)];
+                errors[row] = errors[row] + difference * difference;
+            }
+        }
+        errors
     }
 }
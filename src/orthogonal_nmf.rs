@@ -2,9 +2,39 @@ use std::ops::{Mul, Add};
 
 use nalgebra::{DMat, Transpose};
 use rand::{Rand, Rng, Closed01};
-use num::{Float, Zero};
+use num::{Float, Zero, One};
 
-use helpers::random01;
+use helpers::{random01, canonicalize_factors};
+use initializer::{Initializer, initialize_dmat};
+
+/// returns nonnegative weights for new `data` with `hidden` kept fixed.
+/// does `niterations` multiplicative updates of the weights only.
+/// one observed per column.
+/// one sample per row.
+pub fn transform<FloatT: Float>(hidden: &DMat<FloatT>, data: &DMat<FloatT>, niterations: usize) -> DMat<FloatT> {
+    assert_eq!(hidden.ncols(), data.ncols());
+
+    let hidden_transposed = hidden.transpose();
+    // hidden stays fixed so these stay fixed as well
+    let dividend = data.clone().mul(&hidden_transposed);
+    let partial = hidden.clone().mul(&hidden_transposed);
+
+    let mut weights = DMat::from_elem(data.nrows(), hidden.nrows(), FloatT::one());
+    for _ in 0..niterations {
+        let divisor = weights.clone().mul(&partial);
+        for col in 0..weights.ncols() {
+            for row in 0..weights.nrows() {
+                let index = (row, col);
+                let mut div = divisor[index];
+                if FloatT::zero() == div {
+                    div = FloatT::min_positive_value();
+                }
+                weights[index] = weights[index] * dividend[index] / div;
+            }
+        }
+    }
+    weights
+}
 
 pub struct OrthogonalNMF<FloatT> {
     // TODO add docstrings
@@ -30,6 +60,16 @@ impl<FloatT> OrthogonalNMF<FloatT>
         Self::init(hidden, weights)
     }
 
+    /// lets `initializer` choose the initial `hidden` and `weights`
+    /// for a factorization of `data` with `nhidden` hidden variables.
+    /// one observed per column.
+    /// one sample per row.
+    pub fn init_with<I: Initializer, R: Rng>(
+        initializer: &I, data: &DMat<FloatT>, nhidden: usize, rng: &mut R) -> OrthogonalNMF<FloatT> {
+        let (hidden, weights) = initialize_dmat(initializer, data, nhidden, rng);
+        Self::init(hidden, weights)
+    }
+
     // TODO initialize with values from last time step t-1 instead of choosing randomly
     pub fn init(hidden: DMat<FloatT>, weights: DMat<FloatT>) -> OrthogonalNMF<FloatT> {
         OrthogonalNMF {
@@ -56,6 +96,23 @@ impl<FloatT> OrthogonalNMF<FloatT>
         self.weights.nrows()
     }
 
+    /// rescales each row of `hidden` to unit norm
+    /// moving the scale into the corresponding column of `weights`.
+    /// then sorts the hidden variables by their total contribution
+    /// in descending order.
+    /// does not change `weights * hidden`.
+    /// see `helpers::canonicalize_factors`.
+    pub fn canonicalize(&mut self) {
+        let (nhidden, nobserved, nsamples) = (self.nhidden(), self.nobserved(), self.nsamples());
+        canonicalize_factors(&mut self.hidden, &mut self.weights, nhidden, nobserved, nsamples);
+    }
+
+    /// returns nonnegative weights for new `data` with `hidden` kept fixed.
+    /// see the free function `transform`.
+    pub fn transform(&self, data: &DMat<FloatT>, niterations: usize) -> DMat<FloatT> {
+        transform(&self.hidden, data, niterations)
+    }
+
     // TODO how many iterations ?
     // TODO compare this to the seoung solution
     /// it gets better and better with each iteration.
@@ -76,15 +133,7 @@ impl<FloatT> OrthogonalNMF<FloatT>
         // has the same shape as hidden
         let new_hidden_dividend = weights_transposed.clone().mul(data);
 
-        // gamma is a symetric matrix with diagonal elements equal to zero
-        // and other elements = alpha
-        let gamma_size = self.nhidden();
-        let mut gamma = DMat::from_elem(gamma_size, gamma_size, alpha);
-
-        // set diagonal to zero
-        for i in 0..gamma_size {
-            gamma[(i, i)] = FloatT::zero();
-        }
+        let gamma = Self::gamma(self.nhidden(), alpha);
 
         // has the same shape as hidden
         let new_hidden_divisor = weights_transposed.clone()
@@ -94,6 +143,89 @@ impl<FloatT> OrthogonalNMF<FloatT>
             // multiplied by alpha except for the diag which is set to zero
             .add(gamma.mul(&self.hidden));
 
+        self.update(
+            &new_weights_dividend,
+            &new_weights_divisor,
+            &new_hidden_dividend,
+            &new_hidden_divisor);
+    }
+
+    /// like `iterate` but only the entries of `data`
+    /// that have a nonzero weight in `mask` contribute to the updates.
+    /// `mask` has the same shape as `data`.
+    /// missing entries of `data` may hold any value (even `NaN`).
+    pub fn iterate_masked(&mut self, alpha: FloatT, data: &DMat<FloatT>, mask: &DMat<FloatT>) {
+        assert_eq!(self.nsamples(), data.nrows());
+        assert_eq!(self.nobserved(), data.ncols());
+        assert_eq!(data.nrows(), mask.nrows());
+        assert_eq!(data.ncols(), mask.ncols());
+
+        let hidden_transposed = self.hidden.transpose();
+        let weights_transposed = self.weights.transpose();
+
+        let masked_data = Self::apply_mask(data.clone(), mask);
+        let masked_reconstruction = Self::apply_mask(
+            self.weights.clone().mul(&self.hidden), mask);
+
+        // has the same shape as weights
+        let new_weights_dividend = masked_data.clone().mul(&hidden_transposed);
+        // has the same shape as weights
+        let new_weights_divisor = masked_reconstruction.clone().mul(&hidden_transposed);
+
+        // has the same shape as hidden
+        let new_hidden_dividend = weights_transposed.clone().mul(&masked_data);
+
+        let gamma = Self::gamma(self.nhidden(), alpha);
+
+        // has the same shape as hidden
+        let new_hidden_divisor = weights_transposed.clone()
+            .mul(&masked_reconstruction)
+            .add(gamma.mul(&self.hidden));
+
+        self.update(
+            &new_weights_dividend,
+            &new_weights_divisor,
+            &new_hidden_dividend,
+            &new_hidden_divisor);
+    }
+
+    /// gamma is a symetric matrix with diagonal elements equal to zero
+    /// and other elements = alpha
+    fn gamma(size: usize, alpha: FloatT) -> DMat<FloatT> {
+        let mut gamma = DMat::from_elem(size, size, alpha);
+
+        // set diagonal to zero
+        for i in 0..size {
+            gamma[(i, i)] = FloatT::zero();
+        }
+        gamma
+    }
+
+    /// `input(i,j) <- input(i,j) * mask(i,j)`.
+    /// entries where `mask` is zero are set to zero even if they are `NaN`.
+    fn apply_mask(mut input: DMat<FloatT>, mask: &DMat<FloatT>) -> DMat<FloatT> {
+        for col in 0..input.ncols() {
+            for row in 0..input.nrows() {
+                let index = (row, col);
+                if FloatT::zero() == mask[index] {
+                    input[index] = FloatT::zero();
+                } else {
+                    input[index] = input[index] * mask[index];
+                }
+            }
+        }
+        input
+    }
+
+    /// `weights(i,j) <- weights(i,j) * weights_dividend(i,j) / weights_divisor(i,j)`
+    /// `hidden(i,j) <- hidden(i,j) * hidden_dividend(i,j) / hidden_divisor(i,j)`
+    fn update(
+        &mut self,
+        new_weights_dividend: &DMat<FloatT>,
+        new_weights_divisor: &DMat<FloatT>,
+        new_hidden_dividend: &DMat<FloatT>,
+        new_hidden_divisor: &DMat<FloatT>,
+    ) {
         // compute new weights
         // TODO possibly use simd for this
         for col in 0..self.weights.ncols() {
@@ -0,0 +1,172 @@
+/*!
+dictionary learning on image patches.
+
+overlapping `size` x `size` patches of one or many grayscale images
+become the samples (one flattened patch per row).
+a factorization learns a nonnegative dictionary of patches in `hidden`.
+`dictionary_grid` renders the dictionary.
+`Patches::reconstruct_images` puts reconstructed patches back together
+averaging where they overlap.
+*/
+
+use nalgebra::DMat;
+use image::GrayImage;
+use num::Zero;
+
+use helpers::{Array2D, Normalize};
+use nmf_blas::{FloatT, ShapeAsTuple};
+
+/// returns the brightness of `image` in `0..1`.
+/// one row per pixel row.
+pub fn luma_to_array(image: &GrayImage) -> Array2D<FloatT> {
+    let (width, height) = image.dimensions();
+    let mut array = Array2D::<FloatT>::zeros((height as usize, width as usize));
+    for (x, y, pixel) in image.enumerate_pixels() {
+        array[(y as usize, x as usize)] = pixel[0] as FloatT / 255.;
+    }
+    array
+}
+
+/// returns the start positions of the patches along an axis of `len`.
+/// patches are `stride` apart. the last patch ends at the end of the axis
+/// such that every pixel is covered.
+pub fn patch_starts(len: usize, size: usize, stride: usize) -> Vec<usize> {
+    assert!(size <= len, "patches must not be larger than the image");
+    assert!(0 < stride);
+    let last = len - size;
+    let mut starts: Vec<usize> = (0..last + 1).filter(|start| 0 == start % stride).collect();
+    if starts.last() != Some(&last) {
+        starts.push(last);
+    }
+    starts
+}
+
+/// patches extracted from images together with where they came from
+pub struct Patches {
+    /// one flattened (row by row) patch per row
+    pub samples: Array2D<FloatT>,
+    /// `(image, row, col)` of the top left pixel of each patch
+    pub positions: Vec<(usize, usize, usize)>,
+    /// `(nrows, ncols)` of each image
+    pub image_shapes: Vec<(usize, usize)>,
+    pub size: usize,
+}
+
+impl Patches {
+    /// extracts all `size` x `size` patches `stride` apart from `images`
+    pub fn extract(images: &[Array2D<FloatT>], size: usize, stride: usize) -> Patches {
+        assert!(0 < size);
+        let mut positions = Vec::new();
+        for (iimage, image) in images.iter().enumerate() {
+            let (nrows, ncols) = image.shape_as_tuple();
+            for &row in patch_starts(nrows, size, stride).iter() {
+                for &col in patch_starts(ncols, size, stride).iter() {
+                    positions.push((iimage, row, col));
+                }
+            }
+        }
+
+        let mut samples = Array2D::<FloatT>::zeros((positions.len(), size * size));
+        for (ipatch, &(iimage, row, col)) in positions.iter().enumerate() {
+            for y in 0..size {
+                for x in 0..size {
+                    samples[(ipatch, y * size + x)] = images[iimage][(row + y, col + x)];
+                }
+            }
+        }
+
+        Patches {
+            samples: samples,
+            positions: positions,
+            image_shapes: images.iter().map(|image| image.shape_as_tuple()).collect(),
+            size: size,
+        }
+    }
+
+    /// returns the images put together from `patches`
+    /// (for example `NMFBlas::reconstruction`)
+    /// which are in the same order as `samples`.
+    /// pixels covered by several patches get their average.
+    pub fn reconstruct_images(&self, patches: &Array2D<FloatT>) -> Vec<Array2D<FloatT>> {
+        assert_eq!(patches.shape_as_tuple(), self.samples.shape_as_tuple());
+        let size = self.size;
+        let mut images: Vec<Array2D<FloatT>> = self.image_shapes.iter()
+            .map(|&shape| Array2D::<FloatT>::zeros(shape))
+            .collect();
+        let mut counts: Vec<Array2D<FloatT>> = images.clone();
+
+        for (ipatch, &(iimage, row, col)) in self.positions.iter().enumerate() {
+            for y in 0..size {
+                for x in 0..size {
+                    images[iimage][(row + y, col + x)] += patches[(ipatch, y * size + x)];
+                    counts[iimage][(row + y, col + x)] += 1.;
+                }
+            }
+        }
+
+        for (image, count) in images.iter_mut().zip(counts.iter()) {
+            for (x, n) in image.iter_mut().zip(count.iter()) {
+                if FloatT::zero() < *n {
+                    *x /= *n;
+                }
+            }
+        }
+        images
+    }
+}
+
+/// returns an image of the patches in the rows of `hidden`
+/// tiled into a grid with `ncols` columns.
+/// each patch is normalized by its maximum.
+/// the patches are separated by `padding` white pixels.
+pub fn dictionary_grid(hidden: &Array2D<FloatT>, size: usize, ncols: usize, padding: usize) -> DMat<FloatT> {
+    let (nhidden, nobserved) = hidden.shape_as_tuple();
+    assert_eq!(nobserved, size * size);
+    assert!(0 < ncols);
+    let nrows = (nhidden + ncols - 1) / ncols;
+    let tile = size + padding;
+
+    let mut grid = DMat::from_elem(
+        nrows * tile + padding, ncols * tile + padding, 1. as FloatT);
+    for ihidden in 0..nhidden {
+        let mut patch = DMat::new_zeros(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                patch[(y, x)] = hidden[(ihidden, y * size + x)];
+            }
+        }
+        let patch = patch.normalize();
+        let top = padding + (ihidden / ncols) * tile;
+        let left = padding + (ihidden % ncols) * tile;
+        for y in 0..size {
+            for x in 0..size {
+                grid[(top + y, left + x)] = patch[(y, x)];
+            }
+        }
+    }
+    grid
+}
+
+#[test]
+fn test_patch_starts_cover_the_axis() {
+    assert_eq!(patch_starts(10, 4, 3), vec![0, 3, 6]);
+    assert_eq!(patch_starts(11, 4, 3), vec![0, 3, 6, 7]);
+    assert_eq!(patch_starts(4, 4, 1), vec![0]);
+}
+
+#[test]
+fn test_extract_and_reconstruct_images() {
+    let mut image = Array2D::<FloatT>::zeros((5, 6));
+    for (index, x) in image.iter_mut().enumerate() {
+        *x = index as FloatT / 30.;
+    }
+    let patches = Patches::extract(&[image.clone()], 3, 2);
+    // 2 rows and 3 cols of patches
+    assert_eq!(patches.samples.shape_as_tuple(), (6, 9));
+    assert_eq!(patches.samples[(1, 0)], image[(0, 2)]);
+
+    let reconstructed = patches.reconstruct_images(&patches.samples);
+    for (a, b) in reconstructed[0].iter().zip(image.iter()) {
+        assert!((a - b).abs() < 1e-6);
+    }
+}
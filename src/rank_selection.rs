@@ -0,0 +1,320 @@
+/*!
+choose the number of hidden variables `nhidden` objectively.
+
+for each candidate `nhidden` the samples are factorized several times.
+each factorization clusters the samples:
+each sample belongs to the hidden variable with the largest weight.
+the consensus matrix holds for each pair of samples
+the fraction of factorizations that put them in the same cluster.
+
+a good `nhidden` gives stable clusterings
+and thereby a consensus matrix close to `0` and `1`.
+this is measured by the cophenetic correlation and the dispersion
+as described in *metagenes and molecular pattern discovery using
+matrix factorization* by Brunet et al.
+and *sparse non-negative matrix factorizations via alternating
+non-negativity-constrained least squares* by Kim and Park.
+
+as a complement `cross_validation_rank_selection` holds out
+random entries of the samples, fits a masked NMF for each candidate
+`nhidden` on the remaining entries and compares the errors
+on the held out entries.
+*/
+
+use rand::{StdRng, SeedableRng};
+
+use helpers::Array2D;
+use completion::{complete, HeldOutError};
+use mask::SparseMask;
+use initializer::Initializer;
+use nmf_blas::{FloatT, ShapeAsTuple};
+use restarts::{fit, restart_seed};
+
+/// how stable the clusterings are for one `nhidden`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConsensusQuality {
+    pub nhidden: usize,
+    /// between `-1` and `1`. higher is better. `1` for a perfect consensus.
+    pub cophenetic_correlation: f64,
+    /// between `0` and `1`. higher is better. `1` for a perfect consensus.
+    pub dispersion: f64,
+}
+
+/// returns for each sample (row of `weights`)
+/// the index of the hidden variable with the largest weight
+pub fn argmax_labels(weights: &Array2D<FloatT>) -> Vec<usize> {
+    let (nsamples, nhidden) = weights.shape_as_tuple();
+    (0..nsamples).map(|isample| {
+        let mut imax = 0;
+        for ihidden in 1..nhidden {
+            if weights[(isample, imax)] < weights[(isample, ihidden)] {
+                imax = ihidden;
+            }
+        }
+        imax
+    }).collect()
+}
+
+/// adds `1` to `consensus(i,j)` for each pair of samples `i` and `j`
+/// that have the same label
+pub fn add_connectivity(labels: &[usize], consensus: &mut Array2D<f64>) {
+    let n = labels.len();
+    assert_eq!(consensus.shape_as_tuple(), (n, n));
+    for i in 0..n {
+        for j in 0..n {
+            if labels[i] == labels[j] {
+                consensus[(i, j)] += 1.;
+            }
+        }
+    }
+}
+
+/// returns the consensus matrix of `nrestarts` factorizations
+/// of `samples` with `nhidden` hidden variables.
+/// restart `i` is seeded with `restart_seed(seed, i)`.
+pub fn consensus_matrix<I: Initializer>(
+    initializer: &I,
+    samples: &Array2D<FloatT>,
+    nhidden: usize,
+    niterations: usize,
+    nrestarts: usize,
+    seed: &[usize],
+) -> Array2D<f64> {
+    assert!(0 < nrestarts);
+    let nsamples = samples.shape()[0];
+    let mut samples = samples.clone();
+    let mut consensus = Array2D::<f64>::zeros((nsamples, nsamples));
+    for irestart in 0..nrestarts {
+        let seed = restart_seed(seed, irestart);
+        let (nmf, _) = fit(initializer, &mut samples, nhidden, niterations, None, &seed[..]);
+        add_connectivity(&argmax_labels(&nmf.weights)[..], &mut consensus);
+    }
+    for x in consensus.iter_mut() {
+        *x /= nrestarts as f64;
+    }
+    consensus
+}
+
+/// returns the cophenetic distances of average linkage
+/// hierarchical clustering of the symmetric `distances`.
+/// the cophenetic distance of two items is the height
+/// at which they first end up in the same cluster.
+pub fn cophenetic_distances(distances: &Array2D<f64>) -> Array2D<f64> {
+    let n = distances.shape()[0];
+    assert_eq!(distances.shape_as_tuple(), (n, n));
+
+    let mut clusters: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+    // distances between the clusters
+    let mut between = distances.clone();
+    let mut cophenetic = Array2D::<f64>::zeros((n, n));
+
+    while 1 < clusters.len() {
+        // find the closest pair of clusters
+        let mut closest = (0, 1);
+        for a in 0..clusters.len() {
+            for b in (a + 1)..clusters.len() {
+                if between[(a, b)] < between[(closest.0, closest.1)] {
+                    closest = (a, b);
+                }
+            }
+        }
+        let (a, b) = closest;
+        let height = between[(a, b)];
+
+        for &i in clusters[a].iter() {
+            for &j in clusters[b].iter() {
+                cophenetic[(i, j)] = height;
+                cophenetic[(j, i)] = height;
+            }
+        }
+
+        // average linkage: the distance to the merged cluster
+        // is the size weighted mean of the distances to its parts
+        let size_a = clusters[a].len() as f64;
+        let size_b = clusters[b].len() as f64;
+        for c in 0..clusters.len() {
+            let merged = (size_a * between[(a, c)] + size_b * between[(b, c)])
+                / (size_a + size_b);
+            between[(a, c)] = merged;
+            between[(c, a)] = merged;
+        }
+        let merged_b = clusters.swap_remove(b);
+        clusters[a].extend(merged_b);
+        // mirror the swap_remove in the distances
+        let last = clusters.len();
+        for c in 0..(last + 1) {
+            between[(b, c)] = between[(last, c)];
+            between[(c, b)] = between[(c, last)];
+        }
+        between[(b, b)] = between[(last, last)];
+    }
+
+    cophenetic
+}
+
+/// returns the pearson correlation between the distances `1 - consensus`
+/// and the cophenetic distances of average linkage clustering on them
+pub fn cophenetic_correlation(consensus: &Array2D<f64>) -> f64 {
+    let n = consensus.shape()[0];
+    let mut distances = consensus.clone();
+    for x in distances.iter_mut() {
+        *x = 1. - *x;
+    }
+    let cophenetic = cophenetic_distances(&distances);
+
+    // only the pairs above the diagonal
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            xs.push(distances[(i, j)]);
+            ys.push(cophenetic[(i, j)]);
+        }
+    }
+    if xs.is_empty() {
+        return 1.;
+    }
+
+    let npairs = xs.len() as f64;
+    let mean_x = xs.iter().fold(0., |sum, x| sum + x) / npairs;
+    let mean_y = ys.iter().fold(0., |sum, y| sum + y) / npairs;
+    let mut covariance = 0.;
+    let mut variance_x = 0.;
+    let mut variance_y = 0.;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x) * (x - mean_x);
+        variance_y += (y - mean_y) * (y - mean_y);
+    }
+    // a perfect consensus has only the distances 0 and 1
+    // which hierarchical clustering reproduces exactly
+    if 0. == variance_x || 0. == variance_y {
+        return 1.;
+    }
+    covariance / (variance_x * variance_y).sqrt()
+}
+
+/// returns `sum(4 * (consensus - 1/2)^2) / n^2`
+pub fn dispersion(consensus: &Array2D<f64>) -> f64 {
+    let n = consensus.shape()[0];
+    let sum = consensus.iter()
+        .fold(0., |sum, x| sum + 4. * (x - 0.5) * (x - 0.5));
+    sum / (n * n) as f64
+}
+
+/// returns the consensus quality for each of the candidates `nhiddens`
+pub fn consensus_rank_selection<I: Initializer>(
+    initializer: &I,
+    samples: &Array2D<FloatT>,
+    nhiddens: &[usize],
+    niterations: usize,
+    nrestarts: usize,
+    seed: &[usize],
+) -> Vec<ConsensusQuality> {
+    nhiddens.iter().map(|&nhidden| {
+        let consensus = consensus_matrix(
+            initializer, samples, nhidden, niterations, nrestarts, seed);
+        ConsensusQuality {
+            nhidden: nhidden,
+            cophenetic_correlation: cophenetic_correlation(&consensus),
+            dispersion: dispersion(&consensus),
+        }
+    }).collect()
+}
+
+/// returns the `nhidden` after which the cophenetic correlation
+/// drops the most. falls back to the largest cophenetic correlation
+/// if it never drops.
+pub fn recommended_rank(qualities: &[ConsensusQuality]) -> Option<usize> {
+    if qualities.is_empty() {
+        return None;
+    }
+    let mut best: Option<(usize, f64)> = None;
+    for pair in qualities.windows(2) {
+        let drop = pair[0].cophenetic_correlation - pair[1].cophenetic_correlation;
+        if 0. < drop && best.map_or(true, |(_, best_drop)| best_drop < drop) {
+            best = Some((pair[0].nhidden, drop));
+        }
+    }
+    best.map(|(nhidden, _)| nhidden).or_else(|| {
+        let mut best = qualities[0];
+        for quality in qualities.iter() {
+            if best.cophenetic_correlation < quality.cophenetic_correlation {
+                best = *quality;
+            }
+        }
+        Some(best.nhidden)
+    })
+}
+
+/// the result of `cross_validation_rank_selection`
+pub struct CrossValidation {
+    /// the error on the held out entries for each candidate `nhidden`
+    pub errors: Vec<(usize, HeldOutError)>,
+    /// the candidate `nhidden` with the lowest held out RMSE
+    pub recommended: usize,
+}
+
+/// holds out `test_fraction` of the finite entries of `samples`,
+/// fits a masked NMF with `niterations` iterations for each
+/// of the candidates `nhiddens` on the other entries
+/// and reports the error on the held out entries.
+/// all candidates are evaluated on the same held out entries.
+/// the result only depends on `seed`.
+pub fn cross_validation_rank_selection(
+    samples: &Array2D<FloatT>,
+    nhiddens: &[usize],
+    niterations: usize,
+    test_fraction: f64,
+    seed: &[usize],
+) -> CrossValidation {
+    assert!(!nhiddens.is_empty());
+
+    let observed = SparseMask::from_finite(samples);
+    let mut samples = samples.clone();
+
+    let errors: Vec<(usize, HeldOutError)> = nhiddens.iter().map(|&nhidden| {
+        // the same seed for each candidate gives the same split
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let completion = complete(
+            &mut samples, &observed, nhidden, niterations, test_fraction, &mut rng);
+        (nhidden, completion.error)
+    }).collect();
+
+    let mut recommended = errors[0];
+    for error in errors.iter() {
+        if error.1.rmse < recommended.1.rmse {
+            recommended = *error;
+        }
+    }
+
+    CrossValidation {
+        errors: errors,
+        recommended: recommended.0,
+    }
+}
+
+#[test]
+fn test_perfect_consensus() {
+    let labels = [0, 0, 1, 1, 1];
+    let mut consensus = Array2D::<f64>::zeros((5, 5));
+    add_connectivity(&labels, &mut consensus);
+    assert_eq!(dispersion(&consensus), 1.);
+    assert_eq!(cophenetic_correlation(&consensus), 1.);
+}
+
+#[test]
+fn test_cophenetic_distances() {
+    // 0 and 1 are close. 2 is far away from both.
+    let mut distances = Array2D::<f64>::zeros((3, 3));
+    distances[(0, 1)] = 1.;
+    distances[(1, 0)] = 1.;
+    distances[(0, 2)] = 4.;
+    distances[(2, 0)] = 4.;
+    distances[(1, 2)] = 6.;
+    distances[(2, 1)] = 6.;
+    let cophenetic = cophenetic_distances(&distances);
+    assert_eq!(cophenetic[(0, 1)], 1.);
+    assert_eq!(cophenetic[(0, 2)], 5.);
+    assert_eq!(cophenetic[(1, 2)], 5.);
+}
@@ -0,0 +1,148 @@
+/*!
+multiplicative updates converge to local minima.
+which local minimum depends on the initialization.
+
+run several independently seeded fits
+and keep the one with the lowest objective.
+*/
+
+use std::thread;
+
+use rand::{StdRng, SeedableRng};
+use num::Float;
+
+use helpers::Array2D;
+use initializer::Initializer;
+use nmf_blas::{NMFBlas, FloatT};
+
+/// how much the objectives of the restarts differ
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjectiveSpread {
+    pub min: FloatT,
+    pub max: FloatT,
+    pub mean: FloatT,
+    pub standard_deviation: FloatT,
+}
+
+impl ObjectiveSpread {
+    pub fn from_objectives(objectives: &[FloatT]) -> ObjectiveSpread {
+        assert!(!objectives.is_empty());
+        let n = objectives.len() as FloatT;
+        let min = objectives.iter().cloned().fold(FloatT::infinity(), FloatT::min);
+        let max = objectives.iter().cloned().fold(FloatT::neg_infinity(), FloatT::max);
+        let mean = objectives.iter().fold(0., |sum, x| sum + x) / n;
+        let variance = objectives.iter()
+            .fold(0., |sum, x| sum + (x - mean) * (x - mean)) / n;
+        ObjectiveSpread {
+            min: min,
+            max: max,
+            mean: mean,
+            standard_deviation: variance.sqrt(),
+        }
+    }
+}
+
+/// the result of `best_of_restarts`
+pub struct Restarts {
+    /// the fit with the lowest objective
+    pub best: NMFBlas,
+    /// index of `best` in `objectives`
+    pub ibest: usize,
+    /// the final objective of each restart in order
+    pub objectives: Vec<FloatT>,
+    pub spread: ObjectiveSpread,
+}
+
+/// returns the seed of restart `irestart`.
+/// `seed` followed by `irestart`.
+pub fn restart_seed(seed: &[usize], irestart: usize) -> Vec<usize> {
+    let mut result = seed.to_vec();
+    result.push(irestart);
+    result
+}
+
+/// initializes with `initializer` and does `niterations` iterations.
+/// returns the fit and its final objective.
+pub fn fit<I: Initializer>(
+    initializer: &I,
+    samples: &mut Array2D<FloatT>,
+    nhidden: usize,
+    niterations: usize,
+    orthogonal_with_alpha: Option<FloatT>,
+    seed: &[usize],
+) -> (NMFBlas, FloatT) {
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+    let mut nmf = NMFBlas::new_with(initializer, samples, nhidden, &mut rng);
+    for _ in 0..niterations {
+        nmf.iterate(samples, orthogonal_with_alpha);
+    }
+    let objective = nmf.squared_error(samples);
+    (nmf, objective)
+}
+
+/// runs `nrestarts` fits of `samples` with `nhidden` hidden variables.
+/// restart `i` is seeded with `restart_seed(seed, i)`
+/// such that the result does not depend on `parallel`.
+/// if `parallel` each restart runs in its own thread
+/// with its own copy of `samples`.
+pub fn best_of_restarts<I>(
+    initializer: &I,
+    samples: &Array2D<FloatT>,
+    nhidden: usize,
+    niterations: usize,
+    orthogonal_with_alpha: Option<FloatT>,
+    nrestarts: usize,
+    seed: &[usize],
+    parallel: bool,
+) -> Restarts
+    where I: Initializer + Clone + Send + 'static
+{
+    assert!(0 < nrestarts);
+
+    let fits: Vec<(NMFBlas, FloatT)> = if parallel {
+        let handles: Vec<_> = (0..nrestarts).map(|irestart| {
+            let initializer = initializer.clone();
+            let mut samples = samples.clone();
+            let seed = restart_seed(seed, irestart);
+            thread::spawn(move || {
+                fit(&initializer, &mut samples, nhidden, niterations,
+                    orthogonal_with_alpha, &seed[..])
+            })
+        }).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    } else {
+        let mut samples = samples.clone();
+        (0..nrestarts).map(|irestart| {
+            let seed = restart_seed(seed, irestart);
+            fit(initializer, &mut samples, nhidden, niterations,
+                orthogonal_with_alpha, &seed[..])
+        }).collect()
+    };
+
+    let objectives: Vec<FloatT> = fits.iter().map(|&(_, objective)| objective).collect();
+    let spread = ObjectiveSpread::from_objectives(&objectives[..]);
+
+    let mut ibest = 0;
+    for (i, objective) in objectives.iter().enumerate() {
+        if *objective < objectives[ibest] {
+            ibest = i;
+        }
+    }
+    let best = fits.into_iter().nth(ibest).unwrap().0;
+
+    Restarts {
+        best: best,
+        ibest: ibest,
+        objectives: objectives,
+        spread: spread,
+    }
+}
+
+#[test]
+fn test_objective_spread() {
+    let spread = ObjectiveSpread::from_objectives(&[1., 3., 2., 2.]);
+    assert_eq!(spread.min, 1.);
+    assert_eq!(spread.max, 3.);
+    assert_eq!(spread.mean, 2.);
+    assert_eq!(spread.standard_deviation, (0.5 as FloatT).sqrt());
+}
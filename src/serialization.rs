@@ -0,0 +1,881 @@
+/*!
+save and load fitted factorizations.
+
+a saved model holds `hidden`, `weights`, the kind of model
+and the hyperparameters used to fit it.
+
+there are two formats:
+
+- a compact little endian binary format
+- a human readable text format
+
+both start with a format version.
+loading fails with an explicit error if the version is not supported,
+if the shapes are inconsistent or if the model is loaded
+as a different kind of model than it was saved as.
+*/
+
+use std;
+use std::io::{Read, Write, BufReader};
+use std::fs::File;
+use std::path::Path;
+use std::fmt;
+use std::mem;
+
+use nalgebra::DMat;
+use num::{Float, NumCast};
+
+use helpers::Array2D;
+use nmf_blas::{NMFBlas, FloatT, ShapeAsTuple};
+use orthogonal_nmf::OrthogonalNMF;
+use online_nmf::OnlineNMF;
+
+/// the version written by this crate.
+/// bump this on every incompatible change of the formats.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// the first bytes of the binary format
+pub const BINARY_MAGIC: &'static [u8] = b"ONMF";
+
+/// the first word of the text format
+pub const TEXT_MAGIC: &'static str = "onmf-model";
+
+/// the first bytes of the binary checkpoint format
+pub const CHECKPOINT_MAGIC: &'static [u8] = b"ONMC";
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ModelLoadError {
+        /// an error has occured when reading from the file
+        Io(err: std::io::Error) {
+            from()
+        }
+        /// the data does not start with the magic bytes or word
+        NotAModel {}
+        /// the data was written by an incompatible version
+        Version(found: u32) {
+            description("unsupported format version")
+            display("unsupported format version {} (expected {})", found, FORMAT_VERSION)
+        }
+        /// the model was saved as another kind of model
+        Kind(found: ModelKind, expected: ModelKind) {
+            display("model of kind {} can't be loaded as {}", found, expected)
+        }
+        /// the shapes of `hidden` and `weights` don't fit together
+        Shape(message: String) {
+            display("shape mismatch: {}", message)
+        }
+        /// the data is malformed
+        Parse(message: String) {
+            display("parse error: {}", message)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModelKind {
+    NMFBlas,
+    OrthogonalNMF,
+    OnlineNMF,
+}
+
+impl ModelKind {
+    fn to_u8(&self) -> u8 {
+        match *self {
+            ModelKind::NMFBlas => 0,
+            ModelKind::OrthogonalNMF => 1,
+            ModelKind::OnlineNMF => 2,
+        }
+    }
+
+    fn from_u8(byte: u8) -> Result<ModelKind, ModelLoadError> {
+        match byte {
+            0 => Ok(ModelKind::NMFBlas),
+            1 => Ok(ModelKind::OrthogonalNMF),
+            2 => Ok(ModelKind::OnlineNMF),
+            _ => Err(ModelLoadError::Parse(format!("unknown model kind {}", byte))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            ModelKind::NMFBlas => "nmf_blas",
+            ModelKind::OrthogonalNMF => "orthogonal_nmf",
+            ModelKind::OnlineNMF => "online_nmf",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<ModelKind, ModelLoadError> {
+        match name {
+            "nmf_blas" => Ok(ModelKind::NMFBlas),
+            "orthogonal_nmf" => Ok(ModelKind::OrthogonalNMF),
+            "online_nmf" => Ok(ModelKind::OnlineNMF),
+            _ => Err(ModelLoadError::Parse(format!("unknown model kind `{}`", name))),
+        }
+    }
+}
+
+impl fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// how the orthogonalization `alpha` changes with the iterations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaSchedule {
+    /// no orthogonalization
+    None,
+    /// the same `alpha` in every iteration
+    Constant(f64),
+    /// `alpha = initial * factor^iteration`
+    Exponential { initial: f64, factor: f64 },
+}
+
+impl AlphaSchedule {
+    /// returns the `alpha` to pass to `NMFBlas::iterate` in `iteration`
+    pub fn alpha(&self, iteration: usize) -> Option<FloatT> {
+        match *self {
+            AlphaSchedule::None => None,
+            AlphaSchedule::Constant(alpha) => Some(alpha as FloatT),
+            AlphaSchedule::Exponential { initial, factor } =>
+                Some((initial * factor.powi(iteration as i32)) as FloatT),
+        }
+    }
+}
+
+/// the objective the model minimizes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    /// `sum((samples - weights * hidden)^2)`
+    SquaredError,
+}
+
+impl Objective {
+    fn to_u8(&self) -> u8 {
+        match *self {
+            Objective::SquaredError => 0,
+        }
+    }
+
+    fn from_u8(byte: u8) -> Result<Objective, ModelLoadError> {
+        match byte {
+            0 => Ok(Objective::SquaredError),
+            _ => Err(ModelLoadError::Parse(format!("unknown objective {}", byte))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Objective::SquaredError => "squared_error",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Objective, ModelLoadError> {
+        match name {
+            "squared_error" => Ok(Objective::SquaredError),
+            _ => Err(ModelLoadError::Parse(format!("unknown objective `{}`", name))),
+        }
+    }
+}
+
+/// everything besides `hidden` and `weights` needed to reproduce a fit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperparameters {
+    pub alpha_schedule: AlphaSchedule,
+    pub objective: Objective,
+    /// the seed of the `StdRng` used for initialization
+    pub seed: Vec<usize>,
+}
+
+/// the number of bytes used per value in the binary format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+/// a model in the form it is saved in
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedModel {
+    pub kind: ModelKind,
+    pub hyperparameters: Hyperparameters,
+    pub precision: Precision,
+    /// shape `(nhidden, nobserved)`
+    pub hidden: Array2D<f64>,
+    /// shape `(nsamples, nhidden)`
+    pub weights: Array2D<f64>,
+}
+
+// little endian helpers for the binary format
+
+fn write_u8<W: Write>(writer: &mut W, value: u8) -> std::io::Result<()> {
+    writer.write_all(&[value])
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> std::io::Result<()> {
+    let bytes = [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ];
+    writer.write_all(&bytes)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> std::io::Result<()> {
+    try!(write_u32(writer, value as u32));
+    write_u32(writer, (value >> 32) as u32)
+}
+
+fn write_f64<W: Write>(writer: &mut W, value: f64) -> std::io::Result<()> {
+    write_u64(writer, unsafe { mem::transmute::<f64, u64>(value) })
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, ModelLoadError> {
+    let mut bytes = [0u8; 1];
+    try!(reader.read_exact(&mut bytes));
+    Ok(bytes[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, ModelLoadError> {
+    let mut bytes = [0u8; 4];
+    try!(reader.read_exact(&mut bytes));
+    Ok((bytes[0] as u32) |
+       ((bytes[1] as u32) << 8) |
+       ((bytes[2] as u32) << 16) |
+       ((bytes[3] as u32) << 24))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, ModelLoadError> {
+    let low = try!(read_u32(reader)) as u64;
+    let high = try!(read_u32(reader)) as u64;
+    Ok(low | (high << 32))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> Result<f64, ModelLoadError> {
+    let bits = try!(read_u64(reader));
+    Ok(unsafe { mem::transmute::<u64, f64>(bits) })
+}
+
+fn write_matrix<W: Write>(
+    writer: &mut W, matrix: &Array2D<f64>, precision: Precision,
+) -> std::io::Result<()> {
+    for value in matrix.iter() {
+        try!(match precision {
+            Precision::F32 => write_u32(writer, unsafe {
+                mem::transmute::<f32, u32>(*value as f32)
+            }),
+            Precision::F64 => write_f64(writer, *value),
+        });
+    }
+    Ok(())
+}
+
+fn read_matrix<R: Read>(
+    reader: &mut R, shape: (usize, usize), precision: Precision,
+) -> Result<Array2D<f64>, ModelLoadError> {
+    let mut matrix = Array2D::<f64>::zeros(shape);
+    for value in matrix.iter_mut() {
+        *value = match precision {
+            Precision::F32 => {
+                let bits = try!(read_u32(reader));
+                unsafe { mem::transmute::<u32, f32>(bits) as f64 }
+            }
+            Precision::F64 => try!(read_f64(reader)),
+        };
+    }
+    Ok(matrix)
+}
+
+/// returns an error unless `nbytes` bytes can hold `hidden` and `weights`
+/// of the shapes declared in a header at `bytes_per_value` bytes per value.
+/// call this before allocating the matrices such that a corrupt
+/// or truncated header can't make us allocate arbitrary amounts of memory.
+fn check_nvalues(
+    nhidden: u64, nobserved: u64, nsamples: u64, bytes_per_value: u64, nbytes: usize,
+) -> Result<(), ModelLoadError> {
+    let needed = nhidden.checked_mul(nobserved)
+        .and_then(|nhidden_values| nsamples.checked_mul(nhidden)
+                  .and_then(|nweights_values| nhidden_values.checked_add(nweights_values)))
+        .and_then(|nvalues| nvalues.checked_mul(bytes_per_value));
+    match needed {
+        Some(needed) if needed <= nbytes as u64 => Ok(()),
+        _ => Err(ModelLoadError::Shape(format!(
+            "nhidden = {}, nobserved = {} and nsamples = {} don't fit into the {} bytes of data",
+            nhidden, nobserved, nsamples, nbytes))),
+    }
+}
+
+impl SavedModel {
+    /// returns the number of hidden variables
+    pub fn nhidden(&self) -> usize {
+        self.hidden.shape()[0]
+    }
+
+    /// returns the number of observed variables
+    pub fn nobserved(&self) -> usize {
+        self.hidden.shape()[1]
+    }
+
+    /// returns the number of samples
+    pub fn nsamples(&self) -> usize {
+        self.weights.shape()[0]
+    }
+
+    /// returns an error unless `self` has `nobserved` observed variables.
+    /// use this to check that a loaded model fits the samples at hand.
+    pub fn expect_nobserved(&self, nobserved: usize) -> Result<(), ModelLoadError> {
+        if self.nobserved() != nobserved {
+            return Err(ModelLoadError::Shape(format!(
+                "model has {} observed variables but {} were expected",
+                self.nobserved(), nobserved)));
+        }
+        Ok(())
+    }
+
+    fn expect_kind(&self, expected: ModelKind) -> Result<(), ModelLoadError> {
+        if self.kind != expected {
+            return Err(ModelLoadError::Kind(self.kind, expected));
+        }
+        Ok(())
+    }
+
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        try!(writer.write_all(BINARY_MAGIC));
+        try!(write_u32(writer, FORMAT_VERSION));
+        try!(write_u8(writer, self.kind.to_u8()));
+        try!(write_u8(writer, self.hyperparameters.objective.to_u8()));
+        match self.hyperparameters.alpha_schedule {
+            AlphaSchedule::None => {
+                try!(write_u8(writer, 0));
+            }
+            AlphaSchedule::Constant(alpha) => {
+                try!(write_u8(writer, 1));
+                try!(write_f64(writer, alpha));
+            }
+            AlphaSchedule::Exponential { initial, factor } => {
+                try!(write_u8(writer, 2));
+                try!(write_f64(writer, initial));
+                try!(write_f64(writer, factor));
+            }
+        }
+        try!(write_u64(writer, self.hyperparameters.seed.len() as u64));
+        for &x in self.hyperparameters.seed.iter() {
+            try!(write_u64(writer, x as u64));
+        }
+        try!(write_u8(writer, match self.precision {
+            Precision::F32 => 4,
+            Precision::F64 => 8,
+        }));
+        try!(write_u64(writer, self.nhidden() as u64));
+        try!(write_u64(writer, self.nobserved() as u64));
+        try!(write_u64(writer, self.nsamples() as u64));
+        try!(write_matrix(writer, &self.hidden, self.precision));
+        write_matrix(writer, &self.weights, self.precision)
+    }
+
+    /// reads everything that is left in `reader`.
+    /// the lengths in the header are checked against the number of bytes read
+    /// before anything is allocated for them.
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<SavedModel, ModelLoadError> {
+        let mut bytes = Vec::new();
+        try!(reader.read_to_end(&mut bytes));
+        let reader = &mut &bytes[..];
+
+        let mut magic = [0u8; 4];
+        try!(reader.read_exact(&mut magic));
+        if &magic[..] != BINARY_MAGIC {
+            return Err(ModelLoadError::NotAModel);
+        }
+        let version = try!(read_u32(reader));
+        if version != FORMAT_VERSION {
+            return Err(ModelLoadError::Version(version));
+        }
+        let kind = try!(ModelKind::from_u8(try!(read_u8(reader))));
+        let objective = try!(Objective::from_u8(try!(read_u8(reader))));
+        let alpha_schedule = match try!(read_u8(reader)) {
+            0 => AlphaSchedule::None,
+            1 => AlphaSchedule::Constant(try!(read_f64(reader))),
+            2 => {
+                let initial = try!(read_f64(reader));
+                let factor = try!(read_f64(reader));
+                AlphaSchedule::Exponential { initial: initial, factor: factor }
+            }
+            byte => return Err(ModelLoadError::Parse(
+                format!("unknown alpha schedule {}", byte))),
+        };
+        let seed_len = try!(read_u64(reader));
+        if seed_len > (reader.len() / 8) as u64 {
+            return Err(ModelLoadError::Parse(format!(
+                "the seed has {} values but only {} bytes are left", seed_len, reader.len())));
+        }
+        let seed_len = seed_len as usize;
+        let mut seed = Vec::with_capacity(seed_len);
+        for _ in 0..seed_len {
+            seed.push(try!(read_u64(reader)) as usize);
+        }
+        let bytes_per_value = try!(read_u8(reader));
+        let precision = match bytes_per_value {
+            4 => Precision::F32,
+            8 => Precision::F64,
+            byte => return Err(ModelLoadError::Parse(
+                format!("unknown precision {}", byte))),
+        };
+        let nhidden = try!(read_u64(reader));
+        let nobserved = try!(read_u64(reader));
+        let nsamples = try!(read_u64(reader));
+        try!(check_nvalues(nhidden, nobserved, nsamples, bytes_per_value as u64, reader.len()));
+        let (nhidden, nobserved, nsamples) = (nhidden as usize, nobserved as usize, nsamples as usize);
+        let hidden = try!(read_matrix(reader, (nhidden, nobserved), precision));
+        let weights = try!(read_matrix(reader, (nsamples, nhidden), precision));
+
+        Ok(SavedModel {
+            kind: kind,
+            hyperparameters: Hyperparameters {
+                alpha_schedule: alpha_schedule,
+                objective: objective,
+                seed: seed,
+            },
+            precision: precision,
+            hidden: hidden,
+            weights: weights,
+        })
+    }
+
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        try!(writeln!(writer, "{} {}", TEXT_MAGIC, FORMAT_VERSION));
+        try!(writeln!(writer, "kind {}", self.kind.name()));
+        try!(writeln!(writer, "objective {}", self.hyperparameters.objective.name()));
+        try!(match self.hyperparameters.alpha_schedule {
+            AlphaSchedule::None =>
+                writeln!(writer, "alpha_schedule none"),
+            AlphaSchedule::Constant(alpha) =>
+                writeln!(writer, "alpha_schedule constant {:?}", alpha),
+            AlphaSchedule::Exponential { initial, factor } =>
+                writeln!(writer, "alpha_schedule exponential {:?} {:?}", initial, factor),
+        });
+        try!(write!(writer, "seed"));
+        for x in self.hyperparameters.seed.iter() {
+            try!(write!(writer, " {}", x));
+        }
+        try!(writeln!(writer, ""));
+        try!(writeln!(writer, "precision {}", match self.precision {
+            Precision::F32 => "f32",
+            Precision::F64 => "f64",
+        }));
+        try!(writeln!(writer, "nhidden {}", self.nhidden()));
+        try!(writeln!(writer, "nobserved {}", self.nobserved()));
+        try!(writeln!(writer, "nsamples {}", self.nsamples()));
+        for &(name, matrix) in [("hidden", &self.hidden), ("weights", &self.weights)].iter() {
+            try!(writeln!(writer, "{}", name));
+            for row in 0..matrix.shape()[0] {
+                for col in 0..matrix.shape()[1] {
+                    if 0 < col {
+                        try!(write!(writer, " "));
+                    }
+                    try!(write!(writer, "{:?}", matrix[(row, col)]));
+                }
+                try!(writeln!(writer, ""));
+            }
+        }
+        Ok(())
+    }
+
+    /// reads everything that is left in `reader`.
+    /// the shapes in the header are checked against the length of the text
+    /// before anything is allocated for them.
+    pub fn read_text<R: Read>(mut reader: R) -> Result<SavedModel, ModelLoadError> {
+        let mut text = String::new();
+        try!(reader.read_to_string(&mut text));
+        let mut lines = text.lines().map(|line| Ok(line.to_string()));
+
+        fn next_line<I>(lines: &mut I, what: &str) -> Result<String, ModelLoadError>
+            where I: Iterator<Item=std::io::Result<String>>
+        {
+            match lines.next() {
+                Some(line) => Ok(try!(line)),
+                None => Err(ModelLoadError::Parse(
+                    format!("unexpected end of data. expected `{}`", what))),
+            }
+        }
+
+        // returns the words after `key` of the next line
+        // which has to start with `key`
+        fn expect_line<I>(lines: &mut I, key: &str) -> Result<Vec<String>, ModelLoadError>
+            where I: Iterator<Item=std::io::Result<String>>
+        {
+            let line = try!(next_line(lines, key));
+            let mut words = line.split_whitespace().map(|word| word.to_string());
+            match words.next() {
+                Some(ref word) if word == key => Ok(words.collect()),
+                _ => Err(ModelLoadError::Parse(format!("expected `{}`", key))),
+            }
+        }
+
+        fn parse<T: std::str::FromStr>(word: &str) -> Result<T, ModelLoadError> {
+            word.parse::<T>().map_err(|_| ModelLoadError::Parse(
+                format!("invalid number `{}`", word)))
+        }
+
+        fn single(words: &[String], key: &str) -> Result<String, ModelLoadError> {
+            if 1 != words.len() {
+                return Err(ModelLoadError::Parse(format!("expected one value for `{}`", key)));
+            }
+            Ok(words[0].clone())
+        }
+
+        let version = match expect_line(&mut lines, TEXT_MAGIC) {
+            Ok(words) => try!(parse::<u32>(&try!(single(&words[..], TEXT_MAGIC))[..])),
+            Err(ModelLoadError::Io(err)) => return Err(ModelLoadError::Io(err)),
+            Err(_) => return Err(ModelLoadError::NotAModel),
+        };
+        if version != FORMAT_VERSION {
+            return Err(ModelLoadError::Version(version));
+        }
+
+        let kind = try!(ModelKind::from_name(
+            &try!(single(&try!(expect_line(&mut lines, "kind"))[..], "kind"))[..]));
+        let objective = try!(Objective::from_name(
+            &try!(single(&try!(expect_line(&mut lines, "objective"))[..], "objective"))[..]));
+        let schedule_words = try!(expect_line(&mut lines, "alpha_schedule"));
+        let alpha_schedule = match (schedule_words.get(0).map(|word| &word[..]), schedule_words.len()) {
+            (Some("none"), 1) => AlphaSchedule::None,
+            (Some("constant"), 2) => AlphaSchedule::Constant(try!(parse(&schedule_words[1][..]))),
+            (Some("exponential"), 3) => AlphaSchedule::Exponential {
+                initial: try!(parse(&schedule_words[1][..])),
+                factor: try!(parse(&schedule_words[2][..])),
+            },
+            _ => return Err(ModelLoadError::Parse("invalid alpha_schedule".to_string())),
+        };
+        let mut seed = Vec::new();
+        for word in try!(expect_line(&mut lines, "seed")).iter() {
+            seed.push(try!(parse::<usize>(&word[..])));
+        }
+        let precision = match &try!(single(&try!(expect_line(&mut lines, "precision"))[..], "precision"))[..] {
+            "f32" => Precision::F32,
+            "f64" => Precision::F64,
+            word => return Err(ModelLoadError::Parse(format!("unknown precision `{}`", word))),
+        };
+        let nhidden: usize = try!(parse(&try!(single(&try!(expect_line(&mut lines, "nhidden"))[..], "nhidden"))[..]));
+        let nobserved: usize = try!(parse(&try!(single(&try!(expect_line(&mut lines, "nobserved"))[..], "nobserved"))[..]));
+        let nsamples: usize = try!(parse(&try!(single(&try!(expect_line(&mut lines, "nsamples"))[..], "nsamples"))[..]));
+        // every value takes at least one character
+        try!(check_nvalues(nhidden as u64, nobserved as u64, nsamples as u64, 1, text.len()));
+
+        let mut matrices = Vec::new();
+        for &(name, shape) in [("hidden", (nhidden, nobserved)), ("weights", (nsamples, nhidden))].iter() {
+            try!(expect_line(&mut lines, name));
+            let mut matrix = Array2D::<f64>::zeros(shape);
+            for row in 0..shape.0 {
+                let line = match lines.next() {
+                    Some(line) => try!(line),
+                    None => return Err(ModelLoadError::Shape(format!(
+                        "{} has fewer than {} rows", name, shape.0))),
+                };
+                let values: Vec<&str> = line.split_whitespace().collect();
+                if values.len() != shape.1 {
+                    return Err(ModelLoadError::Shape(format!(
+                        "row {} of {} has {} values but {} were expected",
+                        row, name, values.len(), shape.1)));
+                }
+                for (col, value) in values.iter().enumerate() {
+                    matrix[(row, col)] = try!(parse(value));
+                }
+            }
+            matrices.push(matrix);
+        }
+        let weights = matrices.pop().unwrap();
+        let hidden = matrices.pop().unwrap();
+
+        Ok(SavedModel {
+            kind: kind,
+            hyperparameters: Hyperparameters {
+                alpha_schedule: alpha_schedule,
+                objective: objective,
+                seed: seed,
+            },
+            precision: precision,
+            hidden: hidden,
+            weights: weights,
+        })
+    }
+
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = try!(File::create(path));
+        self.write_binary(&mut file)
+    }
+
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<SavedModel, ModelLoadError> {
+        let mut file = BufReader::new(try!(File::open(path)));
+        SavedModel::read_binary(&mut file)
+    }
+
+    pub fn save_text<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = try!(File::create(path));
+        self.write_text(&mut file)
+    }
+
+    pub fn load_text<P: AsRef<Path>>(path: P) -> Result<SavedModel, ModelLoadError> {
+        let file = try!(File::open(path));
+        SavedModel::read_text(file)
+    }
+}
+
+/// the full state of a fit that is still running.
+/// the model plus the number of iterations done so far.
+/// the alpha schedule and the seed are part of the hyperparameters
+/// of the model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub model: SavedModel,
+    /// number of iterations done so far
+    pub iteration: usize,
+}
+
+impl Checkpoint {
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        try!(writer.write_all(CHECKPOINT_MAGIC));
+        try!(write_u32(writer, FORMAT_VERSION));
+        try!(write_u64(writer, self.iteration as u64));
+        self.model.write_binary(writer)
+    }
+
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Checkpoint, ModelLoadError> {
+        let mut magic = [0u8; 4];
+        try!(reader.read_exact(&mut magic));
+        if &magic[..] != CHECKPOINT_MAGIC {
+            return Err(ModelLoadError::NotAModel);
+        }
+        let version = try!(read_u32(reader));
+        if version != FORMAT_VERSION {
+            return Err(ModelLoadError::Version(version));
+        }
+        let iteration = try!(read_u64(reader)) as usize;
+        let model = try!(SavedModel::read_binary(reader));
+        Ok(Checkpoint {
+            model: model,
+            iteration: iteration,
+        })
+    }
+
+    /// writes to a temporary file next to `path` first
+    /// and then renames it to `path`.
+    /// a crash during saving never destroys the previous checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        {
+            let mut file = try!(File::create(&tmp_path));
+            try!(self.write_binary(&mut file));
+            try!(file.sync_all());
+        }
+        std::fs::rename(&tmp_path, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, ModelLoadError> {
+        let mut file = BufReader::new(try!(File::open(path)));
+        Checkpoint::read_binary(&mut file)
+    }
+}
+
+fn array_to_f64(array: &Array2D<FloatT>) -> Array2D<f64> {
+    let mut result = Array2D::<f64>::zeros(array.shape_as_tuple());
+    for (index, value) in array.indexed_iter() {
+        result[index] = *value as f64;
+    }
+    result
+}
+
+fn array_from_f64(array: &Array2D<f64>) -> Array2D<FloatT> {
+    let mut result = Array2D::<FloatT>::zeros(array.shape_as_tuple());
+    for (index, value) in array.indexed_iter() {
+        result[index] = *value as FloatT;
+    }
+    result
+}
+
+fn dmat_to_f64<T: Float>(matrix: &DMat<T>) -> Array2D<f64> {
+    let mut result = Array2D::<f64>::zeros((matrix.nrows(), matrix.ncols()));
+    for col in 0..matrix.ncols() {
+        for row in 0..matrix.nrows() {
+            result[(row, col)] = matrix[(row, col)].to_f64().unwrap();
+        }
+    }
+    result
+}
+
+fn dmat_from_f64<T: Float>(array: &Array2D<f64>) -> DMat<T> {
+    let mut result = DMat::from_elem(array.shape()[0], array.shape()[1], T::zero());
+    for ((row, col), value) in array.indexed_iter() {
+        result[(row, col)] = <T as NumCast>::from(*value).unwrap();
+    }
+    result
+}
+
+fn precision_of<T>() -> Precision {
+    if 4 == mem::size_of::<T>() { Precision::F32 } else { Precision::F64 }
+}
+
+impl NMFBlas {
+    pub fn to_saved(&self, hyperparameters: Hyperparameters) -> SavedModel {
+        SavedModel {
+            kind: ModelKind::NMFBlas,
+            hyperparameters: hyperparameters,
+            precision: Precision::F32,
+            hidden: array_to_f64(&self.hidden),
+            weights: array_to_f64(&self.weights),
+        }
+    }
+
+    pub fn from_saved(saved: &SavedModel) -> Result<NMFBlas, ModelLoadError> {
+        try!(saved.expect_kind(ModelKind::NMFBlas));
+        Ok(NMFBlas::new(array_from_f64(&saved.hidden), array_from_f64(&saved.weights)))
+    }
+}
+
+impl<T> OrthogonalNMF<T> where T: Float {
+    pub fn to_saved(&self, hyperparameters: Hyperparameters) -> SavedModel {
+        SavedModel {
+            kind: ModelKind::OrthogonalNMF,
+            hyperparameters: hyperparameters,
+            precision: precision_of::<T>(),
+            hidden: dmat_to_f64(&self.hidden),
+            weights: dmat_to_f64(&self.weights),
+        }
+    }
+
+    pub fn from_saved(saved: &SavedModel) -> Result<OrthogonalNMF<T>, ModelLoadError> {
+        try!(saved.expect_kind(ModelKind::OrthogonalNMF));
+        Ok(OrthogonalNMF {
+            hidden: dmat_from_f64(&saved.hidden),
+            weights: dmat_from_f64(&saved.weights),
+        })
+    }
+}
+
+impl<T> OnlineNMF<T> where T: Float {
+    pub fn to_saved(&self, hyperparameters: Hyperparameters) -> SavedModel {
+        SavedModel {
+            kind: ModelKind::OnlineNMF,
+            hyperparameters: hyperparameters,
+            precision: precision_of::<T>(),
+            hidden: dmat_to_f64(&self.hidden),
+            weights: dmat_to_f64(&self.weights),
+        }
+    }
+
+    pub fn from_saved(saved: &SavedModel) -> Result<OnlineNMF<T>, ModelLoadError> {
+        try!(saved.expect_kind(ModelKind::OnlineNMF));
+        Ok(OnlineNMF {
+            hidden: dmat_from_f64(&saved.hidden),
+            weights: dmat_from_f64(&saved.weights),
+        })
+    }
+}
+
+#[cfg(test)]
+fn example_model() -> SavedModel {
+    let mut hidden = Array2D::<f64>::zeros((2, 3));
+    let mut weights = Array2D::<f64>::zeros((4, 2));
+    for (i, x) in hidden.iter_mut().chain(weights.iter_mut()).enumerate() {
+        *x = i as f64 / 8.;
+    }
+    SavedModel {
+        kind: ModelKind::NMFBlas,
+        hyperparameters: Hyperparameters {
+            alpha_schedule: AlphaSchedule::Exponential { initial: 0.1, factor: 1.01 },
+            objective: Objective::SquaredError,
+            seed: vec![1, 2, 3, 4],
+        },
+        precision: Precision::F32,
+        hidden: hidden,
+        weights: weights,
+    }
+}
+
+#[test]
+fn test_binary_roundtrip() {
+    let model = example_model();
+    let mut bytes = Vec::new();
+    model.write_binary(&mut bytes).unwrap();
+    let loaded = SavedModel::read_binary(&mut &bytes[..]).unwrap();
+    assert_eq!(model, loaded);
+}
+
+#[test]
+fn test_text_roundtrip() {
+    let model = example_model();
+    let mut bytes = Vec::new();
+    model.write_text(&mut bytes).unwrap();
+    let loaded = SavedModel::read_text(&bytes[..]).unwrap();
+    assert_eq!(model, loaded);
+}
+
+#[test]
+fn test_version_mismatch() {
+    let mut bytes = Vec::new();
+    example_model().write_binary(&mut bytes).unwrap();
+    // the version follows the magic bytes
+    bytes[4] = 99;
+    match SavedModel::read_binary(&mut &bytes[..]) {
+        Err(ModelLoadError::Version(99)) => {}
+        other => panic!("expected a version error but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_corrupt_shape_is_an_error() {
+    let mut bytes = Vec::new();
+    example_model().write_binary(&mut bytes).unwrap();
+    // `nsamples` is the last value of the header.
+    // the 2 x 3 values of hidden and the 4 x 2 values of weights follow it
+    let nsamples_offset = bytes.len() - 4 * (2 * 3 + 4 * 2) - 8;
+    for byte in bytes[nsamples_offset..nsamples_offset + 8].iter_mut() {
+        *byte = 0xff;
+    }
+    match SavedModel::read_binary(&mut &bytes[..]) {
+        Err(ModelLoadError::Shape(_)) => {}
+        other => panic!("expected a shape error but got {:?}", other),
+    }
+
+    // a truncated file
+    let mut bytes = Vec::new();
+    example_model().write_binary(&mut bytes).unwrap();
+    bytes.pop();
+    match SavedModel::read_binary(&mut &bytes[..]) {
+        Err(ModelLoadError::Shape(_)) => {}
+        other => panic!("expected a shape error but got {:?}", other),
+    }
+
+    let mut text = Vec::new();
+    example_model().write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap()
+        .replace("nsamples 4", "nsamples 100000000000");
+    match SavedModel::read_text(text.as_bytes()) {
+        Err(ModelLoadError::Shape(_)) => {}
+        other => panic!("expected a shape error but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_checkpoint_roundtrip() {
+    let checkpoint = Checkpoint {
+        model: example_model(),
+        iteration: 1234,
+    };
+    let mut bytes = Vec::new();
+    checkpoint.write_binary(&mut bytes).unwrap();
+    let loaded = Checkpoint::read_binary(&mut &bytes[..]).unwrap();
+    assert_eq!(checkpoint, loaded);
+}
+
+#[test]
+fn test_kind_mismatch() {
+    match OrthogonalNMF::<f64>::from_saved(&example_model()) {
+        Err(ModelLoadError::Kind(ModelKind::NMFBlas, ModelKind::OrthogonalNMF)) => {}
+        Err(other) => panic!("expected a kind error but got {:?}", other),
+        Ok(_) => panic!("expected a kind error"),
+    }
+}
@@ -0,0 +1,391 @@
+/*!
+sparse samples that are never densified.
+
+only the nonzero entries of `samples` are stored.
+all entries that are not stored are `0`.
+this differs from `mask::SparseMask` where entries
+that are not stored are missing.
+
+factorize them with `SparseNMF`.
+it never allocates anything of the shape of `samples`.
+the products with `samples` that `SparseNMF::iterate` needs
+take time proportional to the number of stored entries
+times the number of hidden variables.
+*/
+
+use num::Zero;
+
+use rblas::Gemm;
+use rblas::attribute::Transpose;
+
+use ndarray::blas::AsBlas;
+
+use helpers::{Dims, Array2D};
+use nmf_blas::{FloatT, ShapeAsTuple, gamma, hidden_divisor, add_orthogonalization,
+               update_from_multiplier_and_divisor};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseSamples {
+    pub shape: Dims,
+    /// `(sample, observed, value)` for each stored entry.
+    /// entries stored more than once are summed.
+    pub entries: Vec<(usize, usize, FloatT)>,
+}
+
+impl SparseSamples {
+    /// returns samples of `shape` where all entries are `0`
+    pub fn new(shape: Dims) -> SparseSamples {
+        SparseSamples {
+            shape: shape,
+            entries: Vec::new(),
+        }
+    }
+
+    /// returns the nonzero entries of `samples`
+    pub fn from_dense(samples: &Array2D<FloatT>) -> SparseSamples {
+        let mut sparse = SparseSamples::new(samples.shape_as_tuple());
+        for ((sample, observed), &value) in samples.indexed_iter() {
+            if FloatT::zero() != value {
+                sparse.push(sample, observed, value);
+            }
+        }
+        sparse
+    }
+
+    /// stores `value` at `(sample, observed)`
+    pub fn push(&mut self, sample: usize, observed: usize, value: FloatT) {
+        assert!(sample < self.shape.0);
+        assert!(observed < self.shape.1);
+        self.entries.push((sample, observed, value));
+    }
+
+    /// returns the number of stored entries
+    #[inline]
+    pub fn nstored(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// returns the equivalent dense samples.
+    /// only use this for small samples.
+    pub fn to_dense(&self) -> Array2D<FloatT> {
+        let mut dense = Array2D::<FloatT>::zeros(self.shape);
+        for &(sample, observed, value) in self.entries.iter() {
+            dense[(sample, observed)] += value;
+        }
+        dense
+    }
+}
+
+/// `weights_multiplier <- samples * hidden.transpose()`
+pub fn sparse_weights_multiplier(
+    samples: &SparseSamples,
+    hidden: &Array2D<FloatT>,
+    weights_multiplier: &mut Array2D<FloatT>,
+) {
+    let nhidden = hidden.shape()[0];
+    assert_eq!(samples.shape.1, hidden.shape()[1]);
+    assert_eq!(weights_multiplier.shape_as_tuple(), (samples.shape.0, nhidden));
+
+    for x in weights_multiplier.iter_mut() {
+        *x = FloatT::zero();
+    }
+    for &(sample, observed, value) in samples.entries.iter() {
+        for ihidden in 0..nhidden {
+            weights_multiplier[(sample, ihidden)] += value * hidden[(ihidden, observed)];
+        }
+    }
+}
+
+/// `hidden_multiplier <- weights.transpose() * samples`
+pub fn sparse_hidden_multiplier(
+    weights: &Array2D<FloatT>,
+    samples: &SparseSamples,
+    hidden_multiplier: &mut Array2D<FloatT>,
+) {
+    let nhidden = weights.shape()[1];
+    assert_eq!(samples.shape.0, weights.shape()[0]);
+    assert_eq!(hidden_multiplier.shape_as_tuple(), (nhidden, samples.shape.1));
+
+    for x in hidden_multiplier.iter_mut() {
+        *x = FloatT::zero();
+    }
+    for &(sample, observed, value) in samples.entries.iter() {
+        for ihidden in 0..nhidden {
+            hidden_multiplier[(ihidden, observed)] += weights[(sample, ihidden)] * value;
+        }
+    }
+}
+
+/// `weights_divisor <- weights * (hidden * hidden.transpose())`.
+/// unlike `nmf_blas::weights_divisor` this never builds
+/// the dense `weights * hidden` of the shape of `samples`.
+pub fn small_weights_divisor(
+    weights: &mut Array2D<FloatT>,
+    hidden: &mut Array2D<FloatT>,
+    // temporary space to hold `hidden * hidden.transpose()`
+    tmp: &mut Array2D<FloatT>,
+    weights_divisor: &mut Array2D<FloatT>,
+) {
+    let nhidden = hidden.shape()[0];
+    assert_eq!(weights.shape()[1], nhidden);
+    assert_eq!(tmp.shape_as_tuple(), (nhidden, nhidden));
+    assert_eq!(weights_divisor.shape_as_tuple(), weights.shape_as_tuple());
+
+    // we can't pass hidden twice
+    let mut hidden_copy = hidden.clone();
+
+    // tmp <- hidden * hidden.transpose()
+    Gemm::gemm(
+        &1.,
+        Transpose::NoTrans, &hidden.blas(),
+        Transpose::Trans, &hidden_copy.blas(),
+        &0.,
+        &mut tmp.blas());
+    // weights_divisor <- weights * tmp
+    Gemm::gemm(
+        &1.,
+        Transpose::NoTrans, &weights.blas(),
+        Transpose::NoTrans, &tmp.blas(),
+        &0.,
+        &mut weights_divisor.blas());
+}
+
+/// like `NMFBlas` for `SparseSamples`.
+/// neither `samples` nor `weights * hidden` are ever densified.
+/// so there are no methods that need dense samples.
+pub struct SparseNMF {
+    pub hidden: Array2D<FloatT>,
+    pub weights: Array2D<FloatT>,
+
+    // these hold temporary results during an iteration.
+    // kept in this struct to prevent unnecessary memory allocations.
+    // none has the shape of the samples.
+    pub weights_multiplier: Array2D<FloatT>,
+    pub weights_divisor: Array2D<FloatT>,
+
+    pub hidden_multiplier: Array2D<FloatT>,
+    pub hidden_divisor: Array2D<FloatT>,
+    pub hidden_divisor_partial: Array2D<FloatT>,
+
+    pub gamma: Array2D<FloatT>,
+}
+
+impl SparseNMF {
+    pub fn new(hidden: Array2D<FloatT>, weights: Array2D<FloatT>) -> SparseNMF {
+        let hidden_shape = hidden.shape_as_tuple();
+        let nhidden = hidden_shape.0;
+        let weights_shape = weights.shape_as_tuple();
+        assert!(weights_shape.1 == nhidden, "row count of hidden must be equal to column count of weights");
+
+        SparseNMF {
+            hidden: hidden,
+            weights: weights,
+
+            weights_multiplier: Array2D::<FloatT>::zeros(weights_shape),
+            weights_divisor: Array2D::<FloatT>::zeros(weights_shape),
+
+            hidden_multiplier: Array2D::<FloatT>::zeros(hidden_shape),
+            hidden_divisor: Array2D::<FloatT>::zeros(hidden_shape),
+            hidden_divisor_partial: Array2D::<FloatT>::zeros((nhidden, nhidden)),
+
+            gamma: gamma(nhidden),
+        }
+    }
+
+    /// returns the number of observed variables
+    #[inline]
+    pub fn nobserved(&self) -> usize {
+        self.hidden.shape()[1]
+    }
+
+    /// returns the number of hidden variables
+    #[inline]
+    pub fn nhidden(&self) -> usize {
+        self.hidden.shape()[0]
+    }
+
+    /// returns the number of data points
+    #[inline]
+    pub fn nsamples(&self) -> usize {
+        self.weights.shape()[0]
+    }
+
+    pub fn samples_shape(&self) -> (usize, usize) {
+        (self.nsamples(), self.nobserved())
+    }
+
+    /// returns the reconstructed value of `observed` for `sample`.
+    /// that is entry `(sample, observed)` of `weights * hidden`.
+    pub fn predict(&self, sample: usize, observed: usize) -> FloatT {
+        assert!(sample < self.nsamples());
+        assert!(observed < self.nobserved());
+        let mut sum = FloatT::zero();
+        for ihidden in 0..self.nhidden() {
+            sum += self.weights[(sample, ihidden)] * self.hidden[(ihidden, observed)];
+        }
+        sum
+    }
+
+    /// does one iteration step like `NMFBlas::iterate`
+    pub fn iterate(
+        &mut self,
+        samples: &SparseSamples,
+        orthogonal_with_alpha: Option<FloatT>,
+    ) {
+        assert_eq!(samples.shape, self.samples_shape());
+
+        // weights_multiplier <- samples * hidden.transpose()
+        sparse_weights_multiplier(
+            samples,
+            &self.hidden,
+            &mut self.weights_multiplier);
+
+        // weights_divisor <- weights * (hidden * hidden.transpose())
+        small_weights_divisor(
+            &mut self.weights,
+            &mut self.hidden,
+            &mut self.hidden_divisor_partial,
+            &mut self.weights_divisor);
+
+        // hidden_multiplier <- weights.transpose() * samples
+        sparse_hidden_multiplier(
+            &self.weights,
+            samples,
+            &mut self.hidden_multiplier);
+
+        // hidden_divisor <- weights.transpose() * weights * hidden
+        hidden_divisor(
+            &mut self.weights,
+            &mut self.hidden,
+            &mut self.hidden_divisor_partial,
+            &mut self.hidden_divisor);
+
+        if let Some(alpha) = orthogonal_with_alpha {
+            // hidden_divisor <- hidden_divisor + alpha * gamma * hidden
+            add_orthogonalization(
+                alpha,
+                &mut self.gamma,
+                &mut self.hidden,
+                &mut self.hidden_divisor);
+        }
+
+        update_from_multiplier_and_divisor(
+            &self.weights_multiplier,
+            &self.weights_divisor,
+            &mut self.weights);
+
+        update_from_multiplier_and_divisor(
+            &self.hidden_multiplier,
+            &self.hidden_divisor,
+            &mut self.hidden);
+    }
+
+    /// returns the objective `sum((samples - weights * hidden)^2)`
+    /// that `iterate` minimizes (without orthogonalization).
+    /// uses `|samples - weights * hidden|^2 =
+    /// |samples|^2 - 2 <samples, weights * hidden> + |weights * hidden|^2`
+    /// where the last term is `sum((weights.transpose() * weights) .* (hidden * hidden.transpose()))`.
+    pub fn squared_error(&self, samples: &SparseSamples) -> FloatT {
+        assert_eq!(samples.shape, self.samples_shape());
+        let nhidden = self.nhidden();
+
+        // sum up duplicate entries first such that
+        // `|samples|^2` is computed from the actual values
+        let mut entries = samples.entries.clone();
+        entries.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        let mut summed: Vec<(usize, usize, FloatT)> = Vec::with_capacity(entries.len());
+        for (sample, observed, value) in entries {
+            match summed.last_mut() {
+                Some(last) if (last.0, last.1) == (sample, observed) => {
+                    last.2 += value;
+                    continue;
+                }
+                _ => {}
+            }
+            summed.push((sample, observed, value));
+        }
+
+        let mut sum = FloatT::zero();
+        for &(sample, observed, value) in summed.iter() {
+            sum += value * value - 2. * value * self.predict(sample, observed);
+        }
+
+        for a in 0..nhidden {
+            for b in 0..nhidden {
+                let mut weights_gram = FloatT::zero();
+                for sample in 0..self.nsamples() {
+                    weights_gram += self.weights[(sample, a)] * self.weights[(sample, b)];
+                }
+                let mut hidden_gram = FloatT::zero();
+                for observed in 0..self.nobserved() {
+                    hidden_gram += self.hidden[(a, observed)] * self.hidden[(b, observed)];
+                }
+                sum += weights_gram * hidden_gram;
+            }
+        }
+        sum
+    }
+}
+
+#[test]
+fn test_sparse_products_equal_dense_products() {
+    let mut samples = SparseSamples::new((3, 2));
+    samples.push(0, 1, 2.);
+    samples.push(2, 0, 3.);
+    samples.push(2, 0, 1.);
+    let dense = samples.to_dense();
+    assert_eq!(dense[(2, 0)], 4.);
+
+    let mut hidden = Array2D::<FloatT>::zeros((2, 2));
+    hidden[(0, 0)] = 1.;
+    hidden[(0, 1)] = 2.;
+    hidden[(1, 1)] = 3.;
+    let weights = Array2D::<FloatT>::from_elem((3, 2), 0.5);
+
+    let mut weights_multiplier = Array2D::<FloatT>::zeros((3, 2));
+    sparse_weights_multiplier(&samples, &hidden, &mut weights_multiplier);
+    for sample in 0..3 {
+        for ihidden in 0..2 {
+            let expected = (0..2).map(|observed| dense[(sample, observed)] * hidden[(ihidden, observed)])
+                .fold(0., |a, b| a + b);
+            assert_eq!(weights_multiplier[(sample, ihidden)], expected);
+        }
+    }
+
+    let mut hidden_multiplier = Array2D::<FloatT>::zeros((2, 2));
+    sparse_hidden_multiplier(&weights, &samples, &mut hidden_multiplier);
+    for ihidden in 0..2 {
+        for observed in 0..2 {
+            let expected = (0..3).map(|sample| weights[(sample, ihidden)] * dense[(sample, observed)])
+                .fold(0., |a, b| a + b);
+            assert_eq!(hidden_multiplier[(ihidden, observed)], expected);
+        }
+    }
+}
+
+#[test]
+fn test_sparse_nmf_equals_nmf_blas() {
+    use nmf_blas::NMFBlas;
+
+    let mut dense = Array2D::<FloatT>::zeros((4, 3));
+    dense[(0, 0)] = 1.;
+    dense[(1, 2)] = 2.;
+    dense[(3, 1)] = 3.;
+    let sparse = SparseSamples::from_dense(&dense);
+
+    let hidden = Array2D::<FloatT>::from_elem((2, 3), 0.5);
+    let mut weights = Array2D::<FloatT>::from_elem((4, 2), 0.5);
+    weights[(0, 0)] = 0.25;
+    let mut nmf_dense = NMFBlas::new(hidden.clone(), weights.clone());
+    let mut nmf_sparse = SparseNMF::new(hidden, weights);
+
+    for _ in 0..10 {
+        nmf_dense.iterate(&mut dense, None);
+        nmf_sparse.iterate(&sparse, None);
+    }
+    for (a, b) in nmf_dense.hidden.iter().zip(nmf_sparse.hidden.iter()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+    let dense_error = nmf_dense.squared_error(&dense);
+    assert!((dense_error - nmf_sparse.squared_error(&sparse)).abs() < 1e-3);
+}
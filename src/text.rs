@@ -0,0 +1,252 @@
+/*!
+topic modeling of text documents.
+
+each document becomes a sample (row of `samples`).
+each word of the vocabulary becomes an observed variable (column).
+a factorization of the TF-IDF weighted `samples` gives
+topics as rows of `hidden` and the topic mixture of each document
+as rows of `weights`.
+
+the pipeline:
+
+1. `tokenize_documents` splits documents into lowercase words
+   and removes stop words
+2. `Vocabulary::build` keeps the words that occur
+   in neither too few nor too many documents
+3. `tf_idf` or `tf_idf_sparse` weights the word counts
+4. factorize (for example with `SparseNMF`)
+5. `top_words` and `top_topics` report the result
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use num::Zero;
+
+use helpers::{Array2D, descending_nan_last};
+use nmf_blas::{FloatT, ShapeAsTuple};
+use sparse::SparseSamples;
+use delimited::dominant_observed;
+
+/// a short list of english words that carry no topic
+pub const ENGLISH_STOP_WORDS: &'static [&'static str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and",
+    "any", "are", "as", "at", "be", "because", "been", "before", "being", "below",
+    "between", "both", "but", "by", "can", "could", "did", "do", "does", "doing",
+    "down", "during", "each", "few", "for", "from", "further", "had", "has", "have",
+    "having", "he", "her", "here", "hers", "herself", "him", "himself", "his", "how",
+    "i", "if", "in", "into", "is", "it", "its", "itself", "just", "me",
+    "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off",
+    "on", "once", "only", "or", "other", "our", "ours", "ourselves", "out", "over",
+    "own", "same", "she", "should", "so", "some", "such", "than", "that", "the",
+    "their", "theirs", "them", "themselves", "then", "there", "these", "they", "this", "those",
+    "through", "to", "too", "under", "until", "up", "very", "was", "we", "were",
+    "what", "when", "where", "which", "while", "who", "whom", "why", "will", "with",
+    "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+/// returns `ENGLISH_STOP_WORDS` as a set
+pub fn english_stop_words() -> HashSet<String> {
+    ENGLISH_STOP_WORDS.iter().map(|word| word.to_string()).collect()
+}
+
+/// splits `text` into lowercase words.
+/// a word is a run of alphanumeric characters.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// tokenizes each of `documents` and removes the `stop_words`
+pub fn tokenize_documents<S: AsRef<str>>(documents: &[S], stop_words: &HashSet<String>) -> Vec<Vec<String>> {
+    documents.iter()
+        .map(|document| {
+            tokenize(document.as_ref())
+                .into_iter()
+                .filter(|word| !stop_words.contains(word))
+                .collect()
+        })
+        .collect()
+}
+
+/// the words that become the observed variables
+#[derive(Clone, Debug)]
+pub struct Vocabulary {
+    /// sorted alphabetically. one per observed variable.
+    pub words: Vec<String>,
+    /// the index of each word in `words`
+    pub indices: HashMap<String, usize>,
+    /// the number of documents each word occurs in
+    pub document_frequencies: Vec<usize>,
+}
+
+impl Vocabulary {
+    /// keeps the words that occur in at least `min_document_frequency` documents
+    /// and in at most the fraction `max_document_fraction` of the documents.
+    /// very rare words are noise. very common words carry no topic.
+    pub fn build(
+        documents: &[Vec<String>],
+        min_document_frequency: usize,
+        max_document_fraction: FloatT,
+    ) -> Vocabulary {
+        assert!(0. < max_document_fraction && max_document_fraction <= 1.);
+
+        let mut frequencies: HashMap<&str, usize> = HashMap::new();
+        for document in documents.iter() {
+            let unique: HashSet<&str> = document.iter().map(|word| &word[..]).collect();
+            for word in unique {
+                *frequencies.entry(word).or_insert(0) += 1;
+            }
+        }
+
+        let max_document_frequency = max_document_fraction * documents.len() as FloatT;
+        let mut words: Vec<(String, usize)> = frequencies.into_iter()
+            .filter(|&(_, frequency)| {
+                min_document_frequency <= frequency &&
+                    frequency as FloatT <= max_document_frequency
+            })
+            .map(|(word, frequency)| (word.to_string(), frequency))
+            .collect();
+        words.sort();
+
+        Vocabulary {
+            indices: words.iter()
+                .enumerate()
+                .map(|(index, &(ref word, _))| (word.clone(), index))
+                .collect(),
+            document_frequencies: words.iter().map(|&(_, frequency)| frequency).collect(),
+            words: words.into_iter().map(|(word, _)| word).collect(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// returns the smoothed inverse document frequency of each word
+    /// `ln((1 + ndocuments) / (1 + document frequency)) + 1`
+    pub fn inverse_document_frequencies(&self, ndocuments: usize) -> Vec<FloatT> {
+        self.document_frequencies.iter()
+            .map(|&frequency| {
+                ((1 + ndocuments) as FloatT / (1 + frequency) as FloatT).ln() + 1.
+            })
+            .collect()
+    }
+}
+
+/// returns for each of `documents` the `(word index, TF-IDF)` of its words.
+/// each document has unit euclidean length such that
+/// long documents don't dominate the factorization.
+fn tf_idf_entries(documents: &[Vec<String>], vocabulary: &Vocabulary) -> Vec<Vec<(usize, FloatT)>> {
+    let idf = vocabulary.inverse_document_frequencies(documents.len());
+    documents.iter().map(|document| {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for word in document.iter() {
+            if let Some(&index) = vocabulary.indices.get(word) {
+                *counts.entry(index).or_insert(0) += 1;
+            }
+        }
+        let mut entries: Vec<(usize, FloatT)> = counts.into_iter()
+            .map(|(index, count)| (index, count as FloatT * idf[index]))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let norm = entries.iter().fold(FloatT::zero(), |sum, &(_, x)| sum + x * x).sqrt();
+        if FloatT::zero() < norm {
+            for entry in entries.iter_mut() {
+                entry.1 /= norm;
+            }
+        }
+        entries
+    }).collect()
+}
+
+/// returns the dense TF-IDF `samples` with one row per document
+/// and one column per word of `vocabulary`
+pub fn tf_idf(documents: &[Vec<String>], vocabulary: &Vocabulary) -> Array2D<FloatT> {
+    let mut samples = Array2D::<FloatT>::zeros((documents.len(), vocabulary.len()));
+    for (idocument, entries) in tf_idf_entries(documents, vocabulary).into_iter().enumerate() {
+        for (index, value) in entries {
+            samples[(idocument, index)] = value;
+        }
+    }
+    samples
+}
+
+/// like `tf_idf` but only stores the words that occur in a document.
+/// use this for large corpora.
+pub fn tf_idf_sparse(documents: &[Vec<String>], vocabulary: &Vocabulary) -> SparseSamples {
+    let mut samples = SparseSamples::new((documents.len(), vocabulary.len()));
+    for (idocument, entries) in tf_idf_entries(documents, vocabulary).into_iter().enumerate() {
+        for (index, value) in entries {
+            samples.push(idocument, index, value);
+        }
+    }
+    samples
+}
+
+/// returns for each topic (row of `hidden`) its `n` most important words
+pub fn top_words(hidden: &Array2D<FloatT>, vocabulary: &Vocabulary, n: usize) -> Vec<Vec<(String, FloatT)>> {
+    dominant_observed(hidden, &vocabulary.words[..], n)
+}
+
+/// returns for each document (row of `weights`)
+/// the `(topic, weight)` of its `n` most important topics.
+/// `NaN` weights come last.
+pub fn top_topics(weights: &Array2D<FloatT>, n: usize) -> Vec<Vec<(usize, FloatT)>> {
+    let (ndocuments, ntopics) = weights.shape_as_tuple();
+    (0..ndocuments).map(|idocument| {
+        let mut topics: Vec<(usize, FloatT)> = (0..ntopics)
+            .map(|itopic| (itopic, weights[(idocument, itopic)]))
+            .collect();
+        topics.sort_by(|a, b| descending_nan_last(&a.1, &b.1));
+        topics.truncate(n);
+        topics
+    }).collect()
+}
+
+#[test]
+fn test_tokenize_documents() {
+    let documents = ["The cat sat on the mat.", "Dogs, cats & 2 birds!"];
+    let tokens = tokenize_documents(&documents, &english_stop_words());
+    assert_eq!(tokens, vec![
+        vec!["cat", "sat", "mat"],
+        vec!["dogs", "cats", "2", "birds"],
+    ]);
+}
+
+#[test]
+fn test_vocabulary_and_tf_idf() {
+    let documents = tokenize_documents(&[
+        "apple banana apple",
+        "banana cherry",
+        "banana durian",
+    ], &HashSet::new());
+    // `banana` is in all documents. the others are in one.
+    let vocabulary = Vocabulary::build(&documents, 1, 0.9);
+    assert_eq!(vocabulary.words, vec!["apple", "cherry", "durian"]);
+
+    let samples = tf_idf(&documents, &vocabulary);
+    assert_eq!(samples.shape_as_tuple(), (3, 3));
+    assert!((samples[(0, 0)] - 1.).abs() < 1e-6);
+    assert_eq!(samples[(0, 1)], 0.);
+    assert_eq!(tf_idf_sparse(&documents, &vocabulary).to_dense(), samples);
+}
+
+#[test]
+fn test_top_topics() {
+    let mut weights = Array2D::<FloatT>::zeros((1, 3));
+    weights[(0, 1)] = 2.;
+    weights[(0, 2)] = 1.;
+    assert_eq!(top_topics(&weights, 2), vec![vec![(1, 2.), (2, 1.)]]);
+}
+
+#[test]
+fn test_top_topics_with_nan() {
+    let mut weights = Array2D::<FloatT>::zeros((1, 3));
+    weights[(0, 0)] = ::std::f32::NAN;
+    weights[(0, 2)] = 1.;
+    assert_eq!(top_topics(&weights, 2), vec![vec![(2, 1.), (1, 0.)]]);
+}
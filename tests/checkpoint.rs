@@ -0,0 +1,49 @@
+extern crate rand;
+use rand::{StdRng, SeedableRng};
+
+extern crate onmf;
+use onmf::Fit;
+use onmf::helpers::{Array2D, random01};
+use onmf::initializer::Random01;
+use onmf::serialization::{Hyperparameters, AlphaSchedule, Objective};
+
+/// returns a path in the temp directory that is unique per test and process
+/// such that parallel and repeated test runs don't overwrite each others checkpoints
+fn temp_checkpoint_path(test_name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("onmf-{}-{}.bin", test_name, std::process::id()))
+}
+
+#[test]
+fn test_resumed_fit_equals_uninterrupted_fit() {
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let mut samples = Array2D::<f32>::zeros((20, 8));
+    for x in samples.iter_mut() {
+        *x = random01(&mut rng);
+    }
+
+    let hyperparameters = Hyperparameters {
+        alpha_schedule: AlphaSchedule::Exponential { initial: 0.1, factor: 1.01 },
+        objective: Objective::SquaredError,
+        seed: vec![5, 6, 7, 8],
+    };
+
+    let mut uninterrupted = Fit::new(&Random01, &samples, 3, hyperparameters.clone());
+    for _ in 0..20 {
+        uninterrupted.step(&mut samples);
+    }
+
+    let path = temp_checkpoint_path("test_resumed_fit_equals_uninterrupted_fit");
+    let mut interrupted = Fit::new(&Random01, &samples, 3, hyperparameters);
+    interrupted.run(&mut samples, 10, 3, &path).unwrap();
+
+    let mut resumed = Fit::resume(&path).unwrap();
+    assert_eq!(resumed.iteration, 10);
+    resumed.run(&mut samples, 20, 3, &path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(resumed.iteration, 20);
+    assert_eq!(resumed.nmf.hidden, uninterrupted.nmf.hidden);
+    assert_eq!(resumed.nmf.weights, uninterrupted.nmf.weights);
+}
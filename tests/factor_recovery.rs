@@ -0,0 +1,64 @@
+extern crate nalgebra;
+use nalgebra::DMat;
+
+extern crate rand;
+use rand::{StdRng, SeedableRng};
+
+extern crate onmf;
+use onmf::testimage_generator;
+use onmf::helpers::Array2D;
+use onmf::evaluation::{factors_to_rows, match_factors};
+
+#[test]
+fn test_static_factors_recover_themselves() {
+    let factors: Vec<DMat<f32>> = testimage_generator::static_factors().collect();
+    let truth = factors_to_rows(&factors[..]);
+
+    // the same factors in reverse order and scaled
+    let mut learned = truth.clone();
+    let nfactors = factors.len();
+    for ifactor in 0..nfactors {
+        for iobserved in 0..truth.shape()[1] {
+            learned[(nfactors - 1 - ifactor, iobserved)] =
+                3. * truth[(ifactor, iobserved)];
+        }
+    }
+
+    let recovery = match_factors(&learned, &truth);
+    assert!((recovery.recovery_score - 1.).abs() < 1e-6);
+    for factor_match in recovery.matches.iter() {
+        assert_eq!(factor_match.ilearned, Some(nfactors - 1 - factor_match.itruth));
+    }
+}
+
+#[test]
+fn test_nmf_recovers_testimage_factors() {
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    // during the first step the evolving factors equal
+    // two of the static factors.
+    // so the images are random combinations of the static factors only.
+    let nsamples = 200;
+    let factors: Vec<DMat<f32>> = testimage_generator::static_factors().collect();
+    let truth = factors_to_rows(&factors[..]);
+    let (nhidden, nobserved) = (truth.shape()[0], truth.shape()[1]);
+
+    let mut samples = Array2D::<f32>::zeros((nsamples, nobserved));
+    for (_, _, isample, image) in testimage_generator::testimages::<f32, _>(nsamples, &mut rng).take(nsamples) {
+        for (iobserved, value) in image.as_vec().iter().enumerate() {
+            samples[(isample, iobserved)] = *value;
+        }
+    }
+
+    let mut nmf = onmf::NMFBlas::new_random01(nhidden, nobserved, nsamples, &mut rng);
+    for _ in 0..1000 {
+        nmf.iterate(&mut samples, None);
+    }
+
+    let recovery = match_factors(&nmf.hidden, &truth);
+    // random factors score around `0.3`.
+    // a regression in the updates would stay far below this.
+    assert!(1. - recovery.recovery_score < 0.2,
+            "recovery score {} is too low", recovery.recovery_score);
+}